@@ -1 +1,18 @@
+pub mod header_iterator;
+pub mod request;
+pub mod request_factory;
+pub mod response;
+pub mod response_factory;
+pub mod server_request;
+pub mod server_request_factory;
+pub mod stream;
+pub mod stream_exception;
+pub mod stream_factory;
+pub mod uploaded_file;
+pub mod uploaded_file_factory;
 pub mod uri;
+pub mod uri_comparator;
+pub mod uri_factory;
+pub mod uri_normalizer;
+pub mod uri_resolver;
+pub mod uri_template;
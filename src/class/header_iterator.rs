@@ -0,0 +1,82 @@
+use crate::header_map::HeaderMap;
+use ext_php_rs::prelude::*;
+use ext_php_rs::zend::ce;
+
+// Walks a message's headers as `name => values` in the order they were
+// added. It holds its own copy of the map, which messages never mutate
+// once built, so a `foreach` is not affected by later `with*` calls.
+#[php_class(name = "Takaram\\Psr7\\Internal\\HeaderIterator")]
+#[implements(ce::iterator())]
+pub struct HeaderIterator {
+    headers: HeaderMap,
+    position: usize,
+}
+
+impl HeaderIterator {
+    pub(crate) fn new(headers: HeaderMap) -> Self {
+        Self {
+            headers,
+            position: 0,
+        }
+    }
+}
+
+#[php_impl]
+impl HeaderIterator {
+    pub fn current(&self) -> Option<Vec<String>> {
+        let (_, values) = self.headers.iter().nth(self.position)?;
+        Some(values.to_vec())
+    }
+
+    pub fn key(&self) -> Option<String> {
+        let (name, _) = self.headers.iter().nth(self.position)?;
+        Some(name.to_string())
+    }
+
+    pub fn next(&mut self) {
+        self.position += 1;
+    }
+
+    pub fn rewind(&mut self) {
+        self.position = 0;
+    }
+
+    pub fn valid(&self) -> bool {
+        self.position < self.headers.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn iterates_in_order() {
+        let mut headers = HeaderMap::new();
+        headers.append("Host", vec!["example.com".into()]).unwrap();
+        headers.append("Accept", vec!["text/html".into()]).unwrap();
+        headers.append("accept", vec!["*/*".into()]).unwrap();
+        let mut iterator = HeaderIterator::new(headers);
+
+        let mut entries = Vec::new();
+        while iterator.valid() {
+            entries.push((iterator.key().unwrap(), iterator.current().unwrap()));
+            iterator.next();
+        }
+        assert_eq!(
+            entries,
+            [
+                ("Host".to_string(), vec!["example.com".to_string()]),
+                (
+                    "Accept".to_string(),
+                    vec!["text/html".to_string(), "*/*".to_string()]
+                ),
+            ]
+        );
+        assert_eq!(iterator.key(), None);
+        assert_eq!(iterator.current(), None);
+
+        iterator.rewind();
+        assert_eq!(iterator.key().as_deref(), Some("Host"));
+    }
+}
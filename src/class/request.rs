@@ -0,0 +1,689 @@
+use crate::class::header_iterator::HeaderIterator;
+use crate::class::server_request_factory::host_authority;
+use crate::class::stream::Stream;
+use crate::class::stream_factory::stream_argument;
+use crate::class::uri::Uri;
+use crate::class::uri_factory::uri_argument;
+use crate::header_map::HeaderMap;
+use crate::message::{header_entries, header_values, headers_from_array, Message};
+use crate::util::{
+    invalid_argument_exception, invalid_serialized_data, is_token, runtime_exception,
+};
+use ext_php_rs::binary::Binary;
+use ext_php_rs::boxed::ZBox;
+use ext_php_rs::prelude::*;
+use ext_php_rs::types::{ZendClassObject, ZendHashTable, Zval};
+use ext_php_rs::zend::ce;
+use std::io;
+
+#[php_class(name = "Takaram\\Psr7\\Internal\\Request")]
+#[implements(ce::aggregate())]
+#[derive(Clone)]
+pub struct Request {
+    method: String,
+    request_target: Option<String>,
+    uri: Uri,
+    message: Message,
+}
+
+impl Request {
+    pub(crate) fn new(method: &str, uri: Uri, message: Message) -> Result<Self, &'static str> {
+        if !is_token(method) {
+            return Err("Invalid HTTP method");
+        }
+        let mut request = Self {
+            method: method.to_string(),
+            request_target: None,
+            uri,
+            message,
+        };
+        if !request.message.headers.contains("Host") {
+            request.update_host_from_uri()?;
+        }
+        Ok(request)
+    }
+
+    fn update_host_from_uri(&mut self) -> Result<(), &'static str> {
+        let host = self.uri.get_host();
+        if host.is_empty() {
+            return Ok(());
+        }
+        let host = match self.uri.get_port() {
+            Some(port) => format!("{host}:{port}"),
+            None => host,
+        };
+        self.message.headers.set_first("Host", vec![host])
+    }
+
+    fn _with_method(&self, method: &str) -> Result<Self, &str> {
+        if !is_token(method) {
+            return Err("Invalid HTTP method");
+        }
+        Ok(Self {
+            method: method.to_string(),
+            ..self.clone()
+        })
+    }
+
+    pub(crate) fn _with_request_target(&self, request_target: &str) -> Result<Self, &str> {
+        if !is_request_target(request_target) {
+            return Err("Invalid request target");
+        }
+        Ok(Self {
+            request_target: Some(request_target.to_string()),
+            ..self.clone()
+        })
+    }
+
+    // The URI with the authority taken from the Host header when it has
+    // none, as for a request built from an origin-form request line. The
+    // scheme, if missing too, is inferred from the port.
+    fn absolute_uri(&self) -> Uri {
+        if !self.uri.get_host().is_empty() {
+            return self.uri.clone();
+        }
+        let Some((authority, _)) = self
+            .message
+            .headers
+            .get("Host")
+            .first()
+            .and_then(|host| host_authority(host))
+        else {
+            return self.uri.clone();
+        };
+        let scheme = match self.uri.get_scheme() {
+            scheme if !scheme.is_empty() => scheme,
+            _ if authority.ends_with(":443") => "https".to_string(),
+            _ => "http".to_string(),
+        };
+        let mut uri = format!("{scheme}://{authority}");
+        let path = self.uri.get_path();
+        if !path.is_empty() && !path.starts_with('/') {
+            uri.push('/');
+        }
+        uri.push_str(&path);
+        for (delimiter, component) in [('?', self.uri.get_query()), ('#', self.uri.get_fragment())]
+        {
+            if !component.is_empty() {
+                uri.push(delimiter);
+                uri.push_str(&component);
+            }
+        }
+        Uri::new(uri).unwrap_or_else(|_| self.uri.clone())
+    }
+
+    fn _with_uri(&self, uri: &Uri, preserve_host: bool) -> Result<Self, &str> {
+        let mut request = Self {
+            uri: uri.clone(),
+            ..self.clone()
+        };
+        if !preserve_host || !request.message.headers.contains("Host") {
+            request.update_host_from_uri()?;
+        }
+        Ok(request)
+    }
+
+    // Backs the method shortcuts. Content-Length is set when the size of
+    // the body is known.
+    fn shortcut(method: &str, uri: Uri, body: Option<Stream>) -> Result<Self, &'static str> {
+        let mut headers = HeaderMap::new();
+        if let Some(size) = body.as_ref().and_then(Stream::get_size) {
+            headers.set("Content-Length", vec![size.to_string()])?;
+        }
+        Self::new(method, uri, Message::new(headers, body, None))
+    }
+
+    fn _to_raw_message(&self) -> io::Result<Vec<u8>> {
+        self.message.to_raw(&format!(
+            "{} {} HTTP/{}",
+            self.method,
+            self.get_request_target(),
+            self.message.protocol_version
+        ))
+    }
+
+    fn with_message(&self, message: Message) -> Self {
+        Self {
+            message,
+            ..self.clone()
+        }
+    }
+
+    // Adds the state for `__serialize()`; ServerRequest extends it.
+    pub(crate) fn serialize_into(&self, data: &mut ZendHashTable) -> PhpResult<()> {
+        data.insert("method", self.method.clone())?;
+        data.insert("requestTarget", self.request_target.clone())?;
+        data.insert("uri", self.uri.clone())?;
+        self.message.insert_fields(data)
+    }
+
+    pub(crate) fn from_serialized(data: &ZendHashTable) -> PhpResult<Self> {
+        let method = data.get("method").and_then(Zval::string);
+        let uri = data.get("uri").and_then(|uri| uri.extract::<&Uri>());
+        let (Some(method), Some(uri)) = (method, uri) else {
+            return Err(invalid_serialized_data("request"));
+        };
+        let message = Message::from_serialized(data)?;
+        let mut request = Self::new(&method, uri.clone(), message)
+            .map_err(|err| PhpException::new(err.into(), 0, invalid_argument_exception()))?;
+        request.request_target = data.get("requestTarget").and_then(Zval::string);
+        Ok(request)
+    }
+}
+
+// Whether the target takes one of the four forms of RFC 9112, section 3.2:
+// origin-form (`/path?query`), absolute-form (`http://host/path`),
+// authority-form (`host:port`, for CONNECT) or asterisk-form (`*`, for
+// server-wide OPTIONS). None of them carries a fragment.
+fn is_request_target(target: &str) -> bool {
+    if target.is_empty() || target.contains(|c: char| c.is_whitespace() || c == '#') {
+        return false;
+    }
+    if target == "*" || target.starts_with('/') {
+        return true;
+    }
+    is_authority_form(target) || is_absolute_form(target)
+}
+
+fn is_authority_form(target: &str) -> bool {
+    if target.contains(['/', '?', '@']) {
+        return false;
+    }
+    match target.rsplit_once(':') {
+        Some((host, port)) => {
+            !host.is_empty() && !port.is_empty() && port.bytes().all(|b| b.is_ascii_digit())
+        }
+        None => false,
+    }
+}
+
+fn is_absolute_form(target: &str) -> bool {
+    let Some((scheme, rest)) = target.split_once(':') else {
+        return false;
+    };
+    let mut chars = scheme.chars();
+    !rest.is_empty()
+        && chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+}
+
+#[php_impl]
+impl Request {
+    pub fn __construct(
+        method: &str,
+        uri: &Zval,
+        headers: Option<&ZendHashTable>,
+        body: Option<&Stream>,
+        version: Option<String>,
+    ) -> PhpResult<Self> {
+        let uri = uri_argument(uri)?;
+        let headers = match headers {
+            Some(headers) => headers_from_array(headers)?,
+            None => HeaderMap::new(),
+        };
+        let message = Message::new(headers, body.cloned(), version);
+        Self::new(method, uri, message)
+            .map_err(|err| PhpException::new(err.into(), 0, invalid_argument_exception()))
+    }
+
+    pub fn get(uri: &Zval) -> PhpResult<Self> {
+        Self::shortcut("GET", uri_argument(uri)?, None)
+            .map_err(|err| PhpException::new(err.into(), 0, invalid_argument_exception()))
+    }
+
+    /// `body` is a string or a Stream.
+    pub fn post(uri: &Zval, body: Option<&Zval>) -> PhpResult<Self> {
+        let body = body.map(stream_argument).transpose()?.unwrap_or_default();
+        Self::shortcut("POST", uri_argument(uri)?, Some(body))
+            .map_err(|err| PhpException::new(err.into(), 0, invalid_argument_exception()))
+    }
+
+    /// `body` is a string or a Stream.
+    pub fn put(uri: &Zval, body: Option<&Zval>) -> PhpResult<Self> {
+        let body = body.map(stream_argument).transpose()?.unwrap_or_default();
+        Self::shortcut("PUT", uri_argument(uri)?, Some(body))
+            .map_err(|err| PhpException::new(err.into(), 0, invalid_argument_exception()))
+    }
+
+    pub fn delete(uri: &Zval) -> PhpResult<Self> {
+        Self::shortcut("DELETE", uri_argument(uri)?, None)
+            .map_err(|err| PhpException::new(err.into(), 0, invalid_argument_exception()))
+    }
+
+    pub fn get_method(&self) -> String {
+        self.method.clone()
+    }
+
+    pub fn with_method(&self, method: &str) -> PhpResult<Self> {
+        self._with_method(method)
+            .map_err(|err| PhpException::new(err.into(), 0, invalid_argument_exception()))
+    }
+
+    pub fn get_request_target(&self) -> String {
+        if let Some(request_target) = &self.request_target {
+            return request_target.clone();
+        }
+        let mut target = self.uri.get_path();
+        if target.is_empty() {
+            target.push('/');
+        }
+        let query = self.uri.get_query();
+        if !query.is_empty() {
+            target.push('?');
+            target.push_str(&query);
+        }
+        target
+    }
+
+    pub fn with_request_target(&self, request_target: &str) -> PhpResult<Self> {
+        self._with_request_target(request_target)
+            .map_err(|err| PhpException::new(err.into(), 0, invalid_argument_exception()))
+    }
+
+    pub fn get_uri(&self) -> Uri {
+        self.uri.clone()
+    }
+
+    /// The URI, completed with the authority from the Host header when it
+    /// has none; without either it is returned as is.
+    pub fn get_absolute_uri(&self) -> Uri {
+        self.absolute_uri()
+    }
+
+    pub fn with_uri(&self, uri: &Uri, preserve_host: Option<bool>) -> PhpResult<Self> {
+        self._with_uri(uri, preserve_host.unwrap_or(false))
+            .map_err(|err| PhpException::new(err.into(), 0, invalid_argument_exception()))
+    }
+
+    pub fn get_protocol_version(&self) -> String {
+        self.message.protocol_version.clone()
+    }
+
+    pub fn with_protocol_version(&self, version: &str) -> Self {
+        self.with_message(self.message.with_protocol_version(version))
+    }
+
+    pub fn get_headers(&self) -> PhpResult<ZBox<ZendHashTable>> {
+        self.message.headers_table()
+    }
+
+    /// The number of distinct header names.
+    pub fn count_headers(&self) -> usize {
+        self.message.headers.len()
+    }
+
+    /// Iterating a message yields its headers as `name => values`.
+    pub fn get_iterator(&self) -> HeaderIterator {
+        HeaderIterator::new(self.message.headers.clone())
+    }
+
+    pub fn has_header(&self, name: &str) -> bool {
+        self.message.headers.contains(name)
+    }
+
+    pub fn get_header(&self, name: &str) -> Vec<String> {
+        self.message.headers.get(name).to_vec()
+    }
+
+    pub fn get_header_line(&self, name: &str) -> String {
+        self.message.headers.line(name)
+    }
+
+    /// The first value of a header, or `default` when it is absent.
+    pub fn get_header_first_value(&self, name: &str, default: Option<String>) -> Option<String> {
+        self.message.headers.get(name).first().cloned().or(default)
+    }
+
+    /// Parses a structured header such as Accept into its elements, e.g.
+    /// `text/html;q=0.8` becomes `['text/html', 'q' => '0.8']`.
+    pub fn get_header_words(&self, name: &str) -> PhpResult<ZBox<ZendHashTable>> {
+        self.message.header_words(name)
+    }
+
+    pub fn with_header(&self, name: &str, value: &Zval) -> PhpResult<Self> {
+        let message = self
+            .message
+            .with_header(name, header_values(value)?)
+            .map_err(|err| PhpException::new(err.into(), 0, invalid_argument_exception()))?;
+        Ok(self.with_message(message))
+    }
+
+    /// Sets a header only when it is not present yet, e.g. a default
+    /// Content-Type.
+    pub fn with_header_if_none(&self, name: &str, value: &Zval) -> PhpResult<Self> {
+        let message = self
+            .message
+            .with_header_if_none(name, header_values(value)?)
+            .map_err(|err| PhpException::new(err.into(), 0, invalid_argument_exception()))?;
+        Ok(self.with_message(message))
+    }
+
+    pub fn with_headers(&self, headers: &ZendHashTable) -> PhpResult<Self> {
+        let message = self
+            .message
+            .with_headers(header_entries(headers)?)
+            .map_err(|err| PhpException::new(err.into(), 0, invalid_argument_exception()))?;
+        Ok(self.with_message(message))
+    }
+
+    pub fn with_added_header(&self, name: &str, value: &Zval) -> PhpResult<Self> {
+        let message = self
+            .message
+            .with_added_header(name, header_values(value)?)
+            .map_err(|err| PhpException::new(err.into(), 0, invalid_argument_exception()))?;
+        Ok(self.with_message(message))
+    }
+
+    pub fn without_header(&self, name: &str) -> Self {
+        self.with_message(self.message.without_header(name))
+    }
+
+    /// Removes every header whose name starts with `prefix`, ignoring
+    /// case, e.g. all `X-Forwarded-*` headers.
+    pub fn without_header_matching(&self, prefix: &str) -> Self {
+        self.with_message(self.message.without_header_matching(prefix))
+    }
+
+    pub fn get_body(&self) -> Stream {
+        self.message.body.clone()
+    }
+
+    /// The whole body, rewinding it first and again afterwards so it can
+    /// be read twice. A body that cannot seek gives what is left of it.
+    pub fn get_body_contents(&self) -> PhpResult<Binary<u8>> {
+        self.message
+            .body
+            .contents_rewound()
+            .map(Binary::from)
+            .map_err(|err| PhpException::new(err.to_string(), 0, runtime_exception()))
+    }
+
+    pub fn with_body(&self, body: &Stream) -> Self {
+        self.with_message(self.message.with_body(body))
+    }
+
+    /// Copies the body into a new memory stream, so the two messages can
+    /// be read independently, e.g. when retrying a request.
+    pub fn with_cloned_body(&self) -> PhpResult<Self> {
+        let message = self
+            .message
+            .with_cloned_body()
+            .map_err(|err| PhpException::new(err.to_string(), 0, runtime_exception()))?;
+        Ok(self.with_message(message))
+    }
+
+    /// The request as an HTTP/1 message: request line, headers and body.
+    pub fn to_raw_message(&self) -> PhpResult<Binary<u8>> {
+        self._to_raw_message()
+            .map(Binary::from)
+            .map_err(|err| PhpException::new(err.to_string(), 0, runtime_exception()))
+    }
+
+    #[rename("__debugInfo")]
+    pub fn debug_info(&self) -> PhpResult<ZBox<ZendHashTable>> {
+        let mut info = ZendHashTable::new();
+        info.insert("method", self.get_method())?;
+        info.insert("requestTarget", self.get_request_target())?;
+        info.insert("uri", self.get_uri())?;
+        self.message.insert_fields(&mut info)?;
+        Ok(info)
+    }
+
+    #[rename("__serialize")]
+    pub fn serialize(&self) -> PhpResult<ZBox<ZendHashTable>> {
+        let mut data = ZendHashTable::new();
+        self.serialize_into(&mut data)?;
+        Ok(data)
+    }
+
+    #[rename("__unserialize")]
+    pub fn unserialize(
+        #[this] this: &mut ZendClassObject<Self>,
+        data: &ZendHashTable,
+    ) -> PhpResult<()> {
+        this.initialize(Self::from_serialized(data)?);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_request(method: &str, uri: &str) -> Request {
+        let message = Message::new(HeaderMap::new(), None, None);
+        Request::new(method, Uri::new(uri).unwrap(), message).unwrap()
+    }
+
+    #[test]
+    fn invalid_method() {
+        let message = Message::new(HeaderMap::new(), None, None);
+        let uri = Uri::new("/").unwrap();
+        assert!(Request::new("GET /", uri, message).is_err());
+    }
+
+    #[test]
+    fn with_method() {
+        let request = new_request("GET", "/").with_method("post").unwrap();
+        assert_eq!(request.get_method(), "post");
+        assert!(request._with_method("").is_err());
+    }
+
+    #[test]
+    fn request_target_from_uri() {
+        let request = new_request("GET", "http://example.com/path?a=b#frag");
+        assert_eq!(request.get_request_target(), "/path?a=b");
+    }
+
+    #[test]
+    fn request_target_omits_fragment() {
+        let request = new_request("GET", "http://host/p#frag");
+        assert_eq!(request.get_request_target(), "/p");
+
+        let uri = Uri::new("http://host/other?q=1#section").unwrap();
+        let request = request._with_uri(&uri, false).unwrap();
+        assert_eq!(request.get_request_target(), "/other?q=1");
+        assert_eq!(request.get_uri().get_fragment(), "section");
+    }
+
+    #[test]
+    fn request_target_empty_path() {
+        let request = new_request("GET", "http://example.com");
+        assert_eq!(request.get_request_target(), "/");
+    }
+
+    #[test]
+    fn with_request_target() {
+        let request = new_request("OPTIONS", "http://example.com/")
+            ._with_request_target("*")
+            .unwrap();
+        assert_eq!(request.get_request_target(), "*");
+        assert!(request._with_request_target("/a b").is_err());
+    }
+
+    #[test]
+    fn request_target_forms() {
+        let request = new_request("GET", "http://example.com/");
+        for target in [
+            "/",
+            "/path?a=b",
+            "http://example.com/path?a=b",
+            "urn:isbn:0451450523",
+            "example.com:443",
+            "[::1]:8080",
+            "*",
+        ] {
+            let with_target = request._with_request_target(target).unwrap();
+            assert_eq!(with_target.get_request_target(), target);
+        }
+        for target in [
+            "",
+            "example.com/path",
+            "example.com",
+            "example.com:",
+            "user@example.com:443",
+            "/path#fragment",
+            "http://example.com/#fragment",
+            "1http://example.com/",
+            "**",
+        ] {
+            assert!(
+                request._with_request_target(target).is_err(),
+                "target: {target}"
+            );
+        }
+    }
+
+    #[test]
+    fn absolute_uri_from_host_header() {
+        let mut headers = HeaderMap::new();
+        headers
+            .set("Host", vec!["example.com:8080".to_string()])
+            .unwrap();
+        let message = Message::new(headers, None, None);
+        let request = Request::new("GET", Uri::new("/p?q=1#f").unwrap(), message).unwrap();
+        assert_eq!(
+            request.absolute_uri().to_string(),
+            "http://example.com:8080/p?q=1#f"
+        );
+
+        let message = request
+            .message
+            .with_header("Host", vec!["[::1]:443".to_string()])
+            .unwrap();
+        let request = request.with_message(message);
+        assert_eq!(request.absolute_uri().to_string(), "https://[::1]/p?q=1#f");
+    }
+
+    #[test]
+    fn absolute_uri_unchanged() {
+        let request = new_request("GET", "/p?q=1");
+        assert_eq!(request.absolute_uri().to_string(), "/p?q=1");
+
+        let request = new_request("GET", "http://example.com/p");
+        assert_eq!(request.absolute_uri().to_string(), "http://example.com/p");
+    }
+
+    #[test]
+    fn host_header_from_uri() {
+        let request = new_request("GET", "http://example.com:8080/");
+        assert_eq!(request.get_header_line("Host"), "example.com:8080");
+        let request = new_request("GET", "https://example.com:443/");
+        assert_eq!(request.get_header_line("host"), "example.com");
+    }
+
+    #[test]
+    fn host_header_kept_when_given() {
+        let mut headers = HeaderMap::new();
+        headers
+            .set("Host", vec!["given.example".to_string()])
+            .unwrap();
+        let message = Message::new(headers, None, None);
+        let uri = Uri::new("http://example.com/").unwrap();
+        let request = Request::new("GET", uri, message).unwrap();
+        assert_eq!(request.get_header_line("Host"), "given.example");
+    }
+
+    #[test]
+    fn with_uri_updates_host() {
+        let uri = Uri::new("http://other.example/").unwrap();
+        let request = new_request("GET", "http://example.com/")
+            ._with_uri(&uri, false)
+            .unwrap();
+        assert_eq!(request.get_header_line("Host"), "other.example");
+        assert_eq!(request.get_uri().to_string(), "http://other.example/");
+    }
+
+    #[test]
+    fn with_uri_preserve_host() {
+        let uri = Uri::new("http://other.example/").unwrap();
+        let request = new_request("GET", "http://example.com/")
+            ._with_uri(&uri, true)
+            .unwrap();
+        assert_eq!(request.get_header_line("Host"), "example.com");
+
+        let request = new_request("GET", "/")._with_uri(&uri, true).unwrap();
+        assert_eq!(request.get_header_line("Host"), "other.example");
+    }
+
+    #[test]
+    fn with_uri_without_host() {
+        let uri = Uri::new("/path").unwrap();
+        let request = new_request("GET", "http://example.com/")
+            ._with_uri(&uri, false)
+            .unwrap();
+        assert_eq!(request.get_header_line("Host"), "example.com");
+    }
+
+    #[test]
+    fn message_methods() {
+        let request = new_request("GET", "/").with_protocol_version("2");
+        assert_eq!(request.get_protocol_version(), "2");
+        let request = request.with_message(
+            request
+                .message
+                .with_header("X-Foo", vec!["a".to_string()])
+                .unwrap()
+                .with_added_header("x-foo", vec!["b".to_string()])
+                .unwrap(),
+        );
+        assert!(request.has_header("X-FOO"));
+        assert_eq!(request.get_header("x-foo"), ["a", "b"]);
+        assert!(!request.without_header("X-Foo").has_header("X-Foo"));
+    }
+
+    #[test]
+    fn get_and_delete_shortcuts() {
+        let uri = Uri::new("http://example.com/a?b").unwrap();
+        let request = Request::shortcut("GET", uri.clone(), None).unwrap();
+        assert_eq!(request.get_method(), "GET");
+        assert_eq!(request.get_uri().to_string(), "http://example.com/a?b");
+        assert_eq!(request.get_header_line("Host"), "example.com");
+        assert!(!request.has_header("Content-Length"));
+        assert_eq!(request.get_body().get_size(), Some(0));
+
+        let request = Request::shortcut("DELETE", uri, None).unwrap();
+        assert_eq!(request.get_method(), "DELETE");
+    }
+
+    #[test]
+    fn post_and_put_shortcuts() {
+        let uri = Uri::new("http://example.com/items").unwrap();
+        let body = Stream::from_bytes(b"name=value".to_vec());
+        let request = Request::shortcut("POST", uri.clone(), Some(body)).unwrap();
+        assert_eq!(request.get_method(), "POST");
+        assert_eq!(request.get_uri().to_string(), "http://example.com/items");
+        assert_eq!(request.get_header_line("Content-Length"), "10");
+        assert_eq!(*request.get_body().to_string(), b"name=value");
+
+        let request = Request::shortcut("PUT", uri, Some(Stream::default())).unwrap();
+        assert_eq!(request.get_method(), "PUT");
+        assert_eq!(request.get_header_line("Content-Length"), "0");
+    }
+
+    #[test]
+    fn default_protocol_version_and_body() {
+        let request = new_request("GET", "/");
+        assert_eq!(request.get_protocol_version(), "1.1");
+        assert_eq!(request.get_body().get_size(), Some(0));
+    }
+
+    #[test]
+    fn to_raw_message() {
+        let request = new_request("POST", "http://example.com/a?b#c").with_message(
+            Message::new(HeaderMap::new(), None, None)
+                .with_header("X-Foo", vec!["1".into(), "2".into()])
+                .unwrap()
+                .with_body(&Stream::from_bytes(b"body".to_vec())),
+        );
+        assert_eq!(
+            request._to_raw_message().unwrap(),
+            b"POST /a?b HTTP/1.1\r\nX-Foo: 1, 2\r\n\r\nbody"
+        );
+        let request = request.with_protocol_version("1.0");
+        assert!(request
+            ._to_raw_message()
+            .unwrap()
+            .starts_with(b"POST /a?b HTTP/1.0\r\n"));
+    }
+}
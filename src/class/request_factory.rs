@@ -0,0 +1,63 @@
+use crate::class::request::Request;
+use crate::class::uri::Uri;
+use crate::class::uri_factory::uri_argument;
+use crate::header_map::HeaderMap;
+use crate::message::Message;
+use crate::util::invalid_argument_exception;
+use ext_php_rs::prelude::*;
+use ext_php_rs::types::Zval;
+
+// PSR-17 RequestFactoryInterface.
+#[php_class(name = "Takaram\\Psr7\\Internal\\RequestFactory")]
+pub struct RequestFactory;
+
+#[php_impl]
+impl RequestFactory {
+    pub fn __construct() -> Self {
+        Self
+    }
+
+    pub fn create_request(&self, method: &str, uri: &Zval) -> PhpResult<Request> {
+        Self::request(method, uri_argument(uri)?)
+            .map_err(|err| PhpException::new(err.into(), 0, invalid_argument_exception()))
+    }
+}
+
+impl RequestFactory {
+    fn request(method: &str, uri: Uri) -> Result<Request, &'static str> {
+        Request::new(method, uri, Message::new(HeaderMap::new(), None, None))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::class::uri_factory::uri_or_string;
+
+    #[test]
+    fn create_request_from_string() {
+        let uri = uri_or_string(None, Some("https://example.com/a?b")).unwrap();
+        let request = RequestFactory::request("GET", uri).unwrap();
+        assert_eq!(request.get_method(), "GET");
+        assert_eq!(request.get_uri().to_string(), "https://example.com/a?b");
+        assert_eq!(request.get_header_line("Host"), "example.com");
+        assert_eq!(request.get_body().get_size(), Some(0));
+    }
+
+    #[test]
+    fn create_request_from_uri() {
+        let uri = Uri::new("http://example.com:8080/").unwrap();
+        let uri = uri_or_string(Some(&uri), None).unwrap();
+        let request = RequestFactory::request("POST", uri).unwrap();
+        assert_eq!(request.get_method(), "POST");
+        assert_eq!(request.get_uri().to_string(), "http://example.com:8080/");
+        assert_eq!(request.get_header_line("Host"), "example.com:8080");
+    }
+
+    #[test]
+    fn create_request_invalid_method() {
+        let uri = Uri::new("/").unwrap();
+        assert!(RequestFactory::request("GET /", uri.clone()).is_err());
+        assert!(RequestFactory::request("", uri).is_err());
+    }
+}
@@ -0,0 +1,501 @@
+use crate::class::header_iterator::HeaderIterator;
+use crate::class::stream::Stream;
+use crate::class::uri::Uri;
+use crate::class::uri_factory::uri_argument;
+use crate::header_map::HeaderMap;
+use crate::message::{fields_table, header_entries, header_values, headers_from_array, Message};
+use crate::util::{invalid_argument_exception, invalid_serialized_data, runtime_exception};
+use ext_php_rs::binary::Binary;
+use ext_php_rs::boxed::ZBox;
+use ext_php_rs::prelude::*;
+use ext_php_rs::types::{ZendClassObject, ZendHashTable, Zval};
+use ext_php_rs::zend::ce;
+use http::StatusCode;
+use std::collections::BTreeMap;
+use std::io;
+use std::sync::RwLock;
+
+// Phrases registered by `setReasonPhrase()`, shared by every thread.
+static REASON_PHRASES: RwLock<BTreeMap<u16, String>> = RwLock::new(BTreeMap::new());
+
+#[php_class(name = "Takaram\\Psr7\\Internal\\Response")]
+#[implements(ce::aggregate())]
+#[derive(Clone)]
+pub struct Response {
+    status_code: u16,
+    reason_phrase: String,
+    message: Message,
+}
+
+impl Response {
+    pub(crate) fn new(status: i64, reason: &str, message: Message) -> Result<Self, &'static str> {
+        let status_code = status_code(status)?;
+        check_reason_phrase(reason)?;
+        Ok(Self {
+            status_code,
+            reason_phrase: reason_phrase(status_code, reason),
+            message,
+        })
+    }
+
+    // An empty phrase removes the override for the code.
+    fn _set_reason_phrase(code: i64, phrase: &str) -> Result<(), &'static str> {
+        let code = status_code(code)?;
+        check_reason_phrase(phrase)?;
+        let mut phrases = REASON_PHRASES
+            .write()
+            .map_err(|_| "Reason phrase table is poisoned")?;
+        if phrase.is_empty() {
+            phrases.remove(&code);
+        } else {
+            phrases.insert(code, phrase.to_string());
+        }
+        Ok(())
+    }
+
+    fn _with_status(&self, code: i64, reason: &str) -> Result<Self, &str> {
+        Self::new(code, reason, self.message.clone())
+    }
+
+    fn redirect_to(location: Uri, status: i64) -> Result<Self, &'static str> {
+        if !(300..=399).contains(&status) {
+            return Err("Redirect status code must be between 300 and 399");
+        }
+        let mut headers = HeaderMap::new();
+        headers.set("Location", vec![location.to_string()])?;
+        Self::new(status, "", Message::new(headers, None, None))
+    }
+
+    fn _to_raw_message(&self) -> io::Result<Vec<u8>> {
+        self.message.to_raw(&format!(
+            "HTTP/{} {} {}",
+            self.message.protocol_version, self.status_code, self.reason_phrase
+        ))
+    }
+
+    fn with_message(&self, message: Message) -> Self {
+        Self {
+            message,
+            ..self.clone()
+        }
+    }
+
+    fn from_serialized(data: &ZendHashTable) -> PhpResult<Self> {
+        let status = data
+            .get("statusCode")
+            .and_then(Zval::long)
+            .ok_or_else(|| invalid_serialized_data("response"))?;
+        let reason = data
+            .get("reasonPhrase")
+            .and_then(Zval::string)
+            .unwrap_or_default();
+        Self::new(status, &reason, Message::from_serialized(data)?)
+            .map_err(|err| PhpException::new(err.into(), 0, invalid_argument_exception()))
+    }
+}
+
+fn status_code(status: i64) -> Result<u16, &'static str> {
+    match u16::try_from(status) {
+        Ok(code @ 100..=599) => Ok(code),
+        _ => Err("Status code must be an integer between 100 and 599"),
+    }
+}
+
+// The phrase ends up on the status line, so it must not break it.
+fn check_reason_phrase(reason: &str) -> Result<(), &'static str> {
+    if reason.contains(['\r', '\n', '\0']) {
+        return Err("Reason phrase must not contain CR, LF or NUL characters");
+    }
+    Ok(())
+}
+
+// Falls back to a phrase registered for the code, then to the
+// IANA-registered one, when none is given.
+fn reason_phrase(status_code: u16, reason: &str) -> String {
+    if !reason.is_empty() {
+        return reason.to_string();
+    }
+    let registered = REASON_PHRASES
+        .read()
+        .ok()
+        .and_then(|phrases| phrases.get(&status_code).cloned());
+    if let Some(phrase) = registered {
+        return phrase;
+    }
+    StatusCode::from_u16(status_code)
+        .ok()
+        .and_then(|code| code.canonical_reason())
+        .unwrap_or("")
+        .to_string()
+}
+
+#[php_impl]
+impl Response {
+    pub fn __construct(
+        status: Option<i64>,
+        headers: Option<&ZendHashTable>,
+        body: Option<&Stream>,
+        version: Option<String>,
+        reason: Option<String>,
+    ) -> PhpResult<Self> {
+        let headers = match headers {
+            Some(headers) => headers_from_array(headers)?,
+            None => HeaderMap::new(),
+        };
+        let message = Message::new(headers, body.cloned(), version);
+        Self::new(
+            status.unwrap_or(200),
+            reason.as_deref().unwrap_or(""),
+            message,
+        )
+        .map_err(|err| PhpException::new(err.into(), 0, invalid_argument_exception()))
+    }
+
+    /// `location` is a string or a Uri.
+    pub fn redirect(location: &Zval, status: Option<i64>) -> PhpResult<Self> {
+        Self::redirect_to(uri_argument(location)?, status.unwrap_or(302))
+            .map_err(|err| PhpException::new(err.into(), 0, invalid_argument_exception()))
+    }
+
+    /// Sets the default phrase for `code` in every response created from
+    /// now on, in all threads; an empty phrase restores the built-in one.
+    pub fn set_reason_phrase(code: i64, phrase: &str) -> PhpResult<()> {
+        Self::_set_reason_phrase(code, phrase)
+            .map_err(|err| PhpException::new(err.into(), 0, invalid_argument_exception()))
+    }
+
+    pub fn get_status_code(&self) -> u16 {
+        self.status_code
+    }
+
+    pub fn with_status(&self, code: i64, reason_phrase: Option<&str>) -> PhpResult<Self> {
+        self._with_status(code, reason_phrase.unwrap_or(""))
+            .map_err(|err| PhpException::new(err.into(), 0, invalid_argument_exception()))
+    }
+
+    pub fn get_reason_phrase(&self) -> String {
+        self.reason_phrase.clone()
+    }
+
+    pub fn get_protocol_version(&self) -> String {
+        self.message.protocol_version.clone()
+    }
+
+    pub fn with_protocol_version(&self, version: &str) -> Self {
+        self.with_message(self.message.with_protocol_version(version))
+    }
+
+    pub fn get_headers(&self) -> PhpResult<ZBox<ZendHashTable>> {
+        self.message.headers_table()
+    }
+
+    /// The number of distinct header names.
+    pub fn count_headers(&self) -> usize {
+        self.message.headers.len()
+    }
+
+    /// Iterating a message yields its headers as `name => values`.
+    pub fn get_iterator(&self) -> HeaderIterator {
+        HeaderIterator::new(self.message.headers.clone())
+    }
+
+    pub fn has_header(&self, name: &str) -> bool {
+        self.message.headers.contains(name)
+    }
+
+    pub fn get_header(&self, name: &str) -> Vec<String> {
+        self.message.headers.get(name).to_vec()
+    }
+
+    pub fn get_header_line(&self, name: &str) -> String {
+        self.message.headers.line(name)
+    }
+
+    /// The first value of a header, or `default` when it is absent.
+    pub fn get_header_first_value(&self, name: &str, default: Option<String>) -> Option<String> {
+        self.message.headers.get(name).first().cloned().or(default)
+    }
+
+    /// Parses a structured header such as Accept into its elements, e.g.
+    /// `text/html;q=0.8` becomes `['text/html', 'q' => '0.8']`.
+    pub fn get_header_words(&self, name: &str) -> PhpResult<ZBox<ZendHashTable>> {
+        self.message.header_words(name)
+    }
+
+    pub fn with_header(&self, name: &str, value: &Zval) -> PhpResult<Self> {
+        let message = self
+            .message
+            .with_header(name, header_values(value)?)
+            .map_err(|err| PhpException::new(err.into(), 0, invalid_argument_exception()))?;
+        Ok(self.with_message(message))
+    }
+
+    /// Sets a header only when it is not present yet, e.g. a default
+    /// Content-Type.
+    pub fn with_header_if_none(&self, name: &str, value: &Zval) -> PhpResult<Self> {
+        let message = self
+            .message
+            .with_header_if_none(name, header_values(value)?)
+            .map_err(|err| PhpException::new(err.into(), 0, invalid_argument_exception()))?;
+        Ok(self.with_message(message))
+    }
+
+    pub fn with_headers(&self, headers: &ZendHashTable) -> PhpResult<Self> {
+        let message = self
+            .message
+            .with_headers(header_entries(headers)?)
+            .map_err(|err| PhpException::new(err.into(), 0, invalid_argument_exception()))?;
+        Ok(self.with_message(message))
+    }
+
+    pub fn with_added_header(&self, name: &str, value: &Zval) -> PhpResult<Self> {
+        let message = self
+            .message
+            .with_added_header(name, header_values(value)?)
+            .map_err(|err| PhpException::new(err.into(), 0, invalid_argument_exception()))?;
+        Ok(self.with_message(message))
+    }
+
+    pub fn without_header(&self, name: &str) -> Self {
+        self.with_message(self.message.without_header(name))
+    }
+
+    /// Removes every header whose name starts with `prefix`, ignoring
+    /// case, e.g. all `X-Forwarded-*` headers.
+    pub fn without_header_matching(&self, prefix: &str) -> Self {
+        self.with_message(self.message.without_header_matching(prefix))
+    }
+
+    pub fn get_body(&self) -> Stream {
+        self.message.body.clone()
+    }
+
+    /// The whole body, rewinding it first and again afterwards so it can
+    /// be read twice. A body that cannot seek gives what is left of it.
+    pub fn get_body_contents(&self) -> PhpResult<Binary<u8>> {
+        self.message
+            .body
+            .contents_rewound()
+            .map(Binary::from)
+            .map_err(|err| PhpException::new(err.to_string(), 0, runtime_exception()))
+    }
+
+    pub fn with_body(&self, body: &Stream) -> Self {
+        self.with_message(self.message.with_body(body))
+    }
+
+    /// Copies the body into a new memory stream, so the two messages can
+    /// be read independently, e.g. when retrying a request.
+    pub fn with_cloned_body(&self) -> PhpResult<Self> {
+        let message = self
+            .message
+            .with_cloned_body()
+            .map_err(|err| PhpException::new(err.to_string(), 0, runtime_exception()))?;
+        Ok(self.with_message(message))
+    }
+
+    /// Sends the body with chunked transfer coding in `toRawMessage()`,
+    /// dropping Content-Length.
+    pub fn with_chunked_body(&self) -> PhpResult<Self> {
+        let message = self
+            .message
+            .with_chunked_body()
+            .map_err(|err| PhpException::new(err.into(), 0, invalid_argument_exception()))?;
+        Ok(self.with_message(message))
+    }
+
+    /// Sets a field sent after a chunked body.
+    pub fn with_trailer(&self, name: &str, value: &Zval) -> PhpResult<Self> {
+        let message = self
+            .message
+            .with_trailer(name, header_values(value)?)
+            .map_err(|err| PhpException::new(err.into(), 0, invalid_argument_exception()))?;
+        Ok(self.with_message(message))
+    }
+
+    pub fn get_trailers(&self) -> PhpResult<ZBox<ZendHashTable>> {
+        fields_table(&self.message.trailers)
+    }
+
+    /// The response as an HTTP/1 message: status line, headers and body.
+    pub fn to_raw_message(&self) -> PhpResult<Binary<u8>> {
+        self._to_raw_message()
+            .map(Binary::from)
+            .map_err(|err| PhpException::new(err.to_string(), 0, runtime_exception()))
+    }
+
+    #[rename("__debugInfo")]
+    pub fn debug_info(&self) -> PhpResult<ZBox<ZendHashTable>> {
+        let mut info = ZendHashTable::new();
+        info.insert("statusCode", i64::from(self.status_code))?;
+        info.insert("reasonPhrase", self.get_reason_phrase())?;
+        self.message.insert_fields(&mut info)?;
+        Ok(info)
+    }
+
+    #[rename("__serialize")]
+    pub fn serialize(&self) -> PhpResult<ZBox<ZendHashTable>> {
+        let mut data = ZendHashTable::new();
+        data.insert("statusCode", i64::from(self.status_code))?;
+        data.insert("reasonPhrase", self.reason_phrase.clone())?;
+        self.message.insert_fields(&mut data)?;
+        Ok(data)
+    }
+
+    #[rename("__unserialize")]
+    pub fn unserialize(
+        #[this] this: &mut ZendClassObject<Self>,
+        data: &ZendHashTable,
+    ) -> PhpResult<()> {
+        this.initialize(Self::from_serialized(data)?);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_response(status: i64, reason: &str) -> Result<Response, &'static str> {
+        Response::new(status, reason, Message::new(HeaderMap::new(), None, None))
+    }
+
+    #[test]
+    fn default_reason_phrase() {
+        let response = new_response(200, "").unwrap();
+        assert_eq!(response.get_status_code(), 200);
+        assert_eq!(response.get_reason_phrase(), "OK");
+    }
+
+    #[test]
+    fn with_status_reason_phrase() {
+        let response = new_response(200, "").unwrap();
+        let not_found = response._with_status(404, "").unwrap();
+        assert_eq!(not_found.get_status_code(), 404);
+        assert_eq!(not_found.get_reason_phrase(), "Not Found");
+
+        let custom = response._with_status(404, "Gone Fishing").unwrap();
+        assert_eq!(custom.get_reason_phrase(), "Gone Fishing");
+    }
+
+    #[test]
+    fn unregistered_status_code() {
+        let response = new_response(599, "").unwrap();
+        assert_eq!(response.get_reason_phrase(), "");
+    }
+
+    #[test]
+    fn registered_reason_phrase() {
+        Response::_set_reason_phrase(598, "Network Read Timeout").unwrap();
+        assert_eq!(
+            new_response(598, "").unwrap().get_reason_phrase(),
+            "Network Read Timeout"
+        );
+        assert_eq!(
+            new_response(598, "Other").unwrap().get_reason_phrase(),
+            "Other"
+        );
+        assert_eq!(new_response(200, "").unwrap().get_reason_phrase(), "OK");
+
+        Response::_set_reason_phrase(597, "Gone Away").unwrap();
+        Response::_set_reason_phrase(597, "").unwrap();
+        assert_eq!(new_response(597, "").unwrap().get_reason_phrase(), "");
+
+        assert!(Response::_set_reason_phrase(600, "Too High").is_err());
+        assert!(Response::_set_reason_phrase(598, "Bad\r\n").is_err());
+    }
+
+    #[test]
+    fn invalid_status_code() {
+        assert!(new_response(99, "").is_err());
+        assert!(new_response(600, "").is_err());
+        assert!(new_response(-1, "").is_err());
+    }
+
+    #[test]
+    fn invalid_reason_phrase() {
+        for reason in ["OK\r\nX-Injected: 1", "OK\n", "O\0K"] {
+            assert!(new_response(200, reason).is_err(), "reason: {reason:?}");
+            let response = new_response(200, "").unwrap();
+            assert!(response._with_status(200, reason).is_err());
+        }
+    }
+
+    #[test]
+    fn redirect() {
+        let location = Uri::new("https://example.com/login?next=%2F").unwrap();
+        let response = Response::redirect_to(location.clone(), 302).unwrap();
+        assert_eq!(response.get_status_code(), 302);
+        assert_eq!(response.get_reason_phrase(), "Found");
+        assert_eq!(
+            response.get_header_line("Location"),
+            "https://example.com/login?next=%2F"
+        );
+
+        let response = Response::redirect_to(Uri::new("/moved").unwrap(), 301).unwrap();
+        assert_eq!(response.get_status_code(), 301);
+        assert_eq!(response.get_header_line("Location"), "/moved");
+
+        for status in [200, 299, 400, 404] {
+            assert!(Response::redirect_to(location.clone(), status).is_err());
+        }
+    }
+
+    #[test]
+    fn with_status_keeps_message() {
+        let response = new_response(200, "").unwrap();
+        let response = response.with_message(
+            response
+                .message
+                .with_header("X-Foo", vec!["bar".to_string()])
+                .unwrap(),
+        );
+        let response = response._with_status(201, "").unwrap();
+        assert_eq!(response.get_header_line("x-foo"), "bar");
+    }
+
+    #[test]
+    fn get_header_first_value() {
+        let response = new_response(200, "").unwrap();
+        let response = response.with_message(
+            response
+                .message
+                .with_header("Cache-Control", vec!["no-cache".into(), "private".into()])
+                .unwrap(),
+        );
+        assert_eq!(
+            response
+                .get_header_first_value("cache-control", None)
+                .as_deref(),
+            Some("no-cache")
+        );
+        assert_eq!(
+            response
+                .get_header_first_value("ETag", Some("\"none\"".into()))
+                .as_deref(),
+            Some("\"none\"")
+        );
+        assert_eq!(response.get_header_first_value("ETag", None), None);
+    }
+
+    #[test]
+    fn to_raw_message() {
+        let response = new_response(200, "").unwrap();
+        let response = response.with_message(
+            response
+                .message
+                .with_header("Set-Cookie", vec!["a=1".into(), "b=2".into()])
+                .unwrap()
+                .with_body(&Stream::from_bytes(b"hello".to_vec())),
+        );
+        assert_eq!(
+            response._to_raw_message().unwrap(),
+            b"HTTP/1.1 200 OK\r\nSet-Cookie: a=1\r\nSet-Cookie: b=2\r\n\r\nhello"
+        );
+        let response = response.with_protocol_version("1.0");
+        assert!(response
+            ._to_raw_message()
+            .unwrap()
+            .starts_with(b"HTTP/1.0 200 OK\r\n"));
+    }
+}
@@ -0,0 +1,45 @@
+use crate::class::response::Response;
+use crate::header_map::HeaderMap;
+use crate::message::Message;
+use crate::util::invalid_argument_exception;
+use ext_php_rs::prelude::*;
+
+// PSR-17 ResponseFactoryInterface.
+#[php_class(name = "Takaram\\Psr7\\Internal\\ResponseFactory")]
+pub struct ResponseFactory;
+
+#[php_impl]
+impl ResponseFactory {
+    pub fn __construct() -> Self {
+        Self
+    }
+
+    pub fn create_response(
+        &self,
+        code: Option<i64>,
+        reason_phrase: Option<&str>,
+    ) -> PhpResult<Response> {
+        let message = Message::new(HeaderMap::new(), None, None);
+        Response::new(code.unwrap_or(200), reason_phrase.unwrap_or(""), message)
+            .map_err(|err| PhpException::new(err.into(), 0, invalid_argument_exception()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn create_response() {
+        let factory = ResponseFactory::__construct();
+        let response = factory.create_response(None, None).unwrap();
+        assert_eq!(response.get_status_code(), 200);
+        assert_eq!(response.get_reason_phrase(), "OK");
+
+        let response = factory
+            .create_response(Some(404), Some("Gone Fishing"))
+            .unwrap();
+        assert_eq!(response.get_status_code(), 404);
+        assert_eq!(response.get_reason_phrase(), "Gone Fishing");
+    }
+}
@@ -0,0 +1,571 @@
+use crate::class::header_iterator::HeaderIterator;
+use crate::class::request::Request;
+use crate::class::server_request_factory::{host_authority, max_body_size};
+use crate::class::stream::Stream;
+use crate::class::uploaded_file::UploadedFile;
+use crate::class::uri::{ParseMode, Uri};
+use crate::header_map::HeaderMap;
+use crate::message::Message;
+use crate::multipart::{self, FileTree};
+use crate::query::QueryValue;
+use crate::util::{invalid_argument_exception, runtime_exception};
+use ext_php_rs::binary::Binary;
+use ext_php_rs::boxed::ZBox;
+use ext_php_rs::prelude::*;
+use ext_php_rs::types::{ZendClassObject, ZendHashTable, Zval};
+use ext_php_rs::zend::ce;
+
+// `None` stands for an empty array, so a request only allocates the
+// tables it uses.
+type Table = Option<ZBox<ZendHashTable>>;
+
+#[php_class(name = "Takaram\\Psr7\\Internal\\ServerRequest")]
+#[implements(ce::aggregate())]
+pub struct ServerRequest {
+    request: Request,
+    server_params: Table,
+    cookie_params: Table,
+    query_params: Table,
+    uploaded_files: Table,
+    parsed_body: Zval,
+    attributes: Table,
+}
+
+impl Clone for ServerRequest {
+    fn clone(&self) -> Self {
+        Self {
+            request: self.request.clone(),
+            server_params: self.server_params.clone(),
+            cookie_params: self.cookie_params.clone(),
+            query_params: self.query_params.clone(),
+            uploaded_files: self.uploaded_files.clone(),
+            parsed_body: self.parsed_body.shallow_clone(),
+            attributes: self.attributes.clone(),
+        }
+    }
+}
+
+impl ServerRequest {
+    pub(crate) fn new(request: Request, server_params: Table) -> Self {
+        Self {
+            request,
+            server_params,
+            cookie_params: None,
+            query_params: None,
+            uploaded_files: None,
+            parsed_body: Zval::new(),
+            attributes: None,
+        }
+    }
+
+    fn with_request(&self, request: Request) -> Self {
+        Self {
+            request,
+            ..self.clone()
+        }
+    }
+
+    fn from_serialized(data: &ZendHashTable) -> PhpResult<Self> {
+        let table = |key| data.get(key).and_then(Zval::array).map(ToOwned::to_owned);
+        Ok(Self {
+            request: Request::from_serialized(data)?,
+            server_params: table("serverParams"),
+            cookie_params: table("cookieParams"),
+            query_params: table("queryParams"),
+            uploaded_files: table("uploadedFiles"),
+            parsed_body: data
+                .get("parsedBody")
+                .map_or_else(Zval::new, Zval::shallow_clone),
+            attributes: table("attributes"),
+        })
+    }
+}
+
+// Parses a raw HTTP/1 request, as read from a socket or a test fixture.
+// The URI comes from an absolute-form target, or is rebuilt from the Host
+// header for the other forms.
+fn parse_raw_request(raw: &[u8]) -> Result<ServerRequest, String> {
+    let (head, body) = split_head(raw).ok_or("Raw message has no end of headers")?;
+    let head = std::str::from_utf8(head).map_err(|_| "Raw message head is not valid UTF-8")?;
+    let mut lines = head
+        .split('\n')
+        .map(|line| line.strip_suffix('\r').unwrap_or(line));
+    let request_line = lines.next().unwrap_or_default();
+    let mut parts = request_line.split(' ');
+    let (Some(method), Some(target), Some(protocol), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err(format!("Invalid request line: {request_line}"));
+    };
+    let version = protocol
+        .strip_prefix("HTTP/")
+        .filter(|version| {
+            !version.is_empty()
+                && version
+                    .bytes()
+                    .all(|byte| byte.is_ascii_digit() || byte == b'.')
+        })
+        .ok_or_else(|| format!("Invalid protocol version: {protocol}"))?;
+
+    let mut headers = HeaderMap::new();
+    for line in lines {
+        let (name, value) = line
+            .split_once(':')
+            .ok_or_else(|| format!("Invalid header line: {line}"))?;
+        headers.append(name, vec![value.to_string()])?;
+    }
+
+    let origin_form = target.starts_with('/');
+    let uri = if !origin_form && target.contains("://") {
+        target.to_string()
+    } else {
+        let authority = headers
+            .get("Host")
+            .first()
+            .and_then(|host| host_authority(host))
+            .map(|(authority, _)| format!("http://{authority}"))
+            .unwrap_or_default();
+        if origin_form {
+            format!("{authority}{target}")
+        } else {
+            authority
+        }
+    };
+    let uri = Uri::new_with_mode(uri, ParseMode::Lenient)?;
+    let body = Stream::from_bytes(body.to_vec());
+    let message = Message::new(headers, Some(body), Some(version.to_string()));
+    let mut request = Request::new(method, uri, message)?;
+    // The authority and asterisk forms do not follow from the URI.
+    if !origin_form && !target.contains("://") {
+        request = request._with_request_target(target)?;
+    }
+    Ok(ServerRequest::new(request, None))
+}
+
+// The head and body around the first empty line, which may end in CRLF or
+// a bare LF.
+fn split_head(raw: &[u8]) -> Option<(&[u8], &[u8])> {
+    raw.iter()
+        .enumerate()
+        .filter(|(_, &byte)| byte == b'\n')
+        .find_map(|(pos, _)| {
+            let rest = &raw[pos + 1..];
+            let body = rest
+                .strip_prefix(b"\r\n")
+                .or_else(|| rest.strip_prefix(b"\n"))?;
+            Some((&raw[..pos], body))
+        })
+}
+
+fn to_array(table: &Table) -> ZBox<ZendHashTable> {
+    table.clone().unwrap_or_else(ZendHashTable::new)
+}
+
+// PSR-7 requires every leaf of the uploaded files tree to be an uploaded
+// file; nested arrays are walked.
+fn validate_uploaded_files(files: &ZendHashTable) -> Result<(), &'static str> {
+    for file in files.values() {
+        match file.array() {
+            Some(files) => validate_uploaded_files(files)?,
+            None if file.extract::<&UploadedFile>().is_some() => {}
+            None => return Err("Invalid leaf in uploaded files structure"),
+        }
+    }
+    Ok(())
+}
+
+fn validate_parsed_body(data: &Zval) -> Result<(), &'static str> {
+    if !(data.is_null() || data.is_array() || data.is_object()) {
+        return Err("Parsed body must be null, an array or an object");
+    }
+    Ok(())
+}
+
+#[php_impl]
+impl ServerRequest {
+    pub fn __construct(
+        method: &str,
+        uri: &Zval,
+        headers: Option<&ZendHashTable>,
+        body: Option<&Stream>,
+        version: Option<String>,
+        server_params: Option<&ZendHashTable>,
+    ) -> PhpResult<Self> {
+        Ok(Self::new(
+            Request::__construct(method, uri, headers, body, version)?,
+            server_params.map(ToOwned::to_owned),
+        ))
+    }
+
+    /// Parses a raw HTTP request: request line, headers, an empty line and
+    /// the body.
+    pub fn from_raw_message(message: Binary<u8>) -> PhpResult<Self> {
+        parse_raw_request(&message).map_err(|err| PhpException::new(err, 0, runtime_exception()))
+    }
+
+    pub fn get_server_params(&self) -> ZBox<ZendHashTable> {
+        to_array(&self.server_params)
+    }
+
+    pub fn get_cookie_params(&self) -> ZBox<ZendHashTable> {
+        to_array(&self.cookie_params)
+    }
+
+    pub fn with_cookie_params(&self, cookies: &ZendHashTable) -> Self {
+        Self {
+            cookie_params: Some(cookies.to_owned()),
+            ..self.clone()
+        }
+    }
+
+    pub fn get_query_params(&self) -> ZBox<ZendHashTable> {
+        to_array(&self.query_params)
+    }
+
+    pub fn with_query_params(&self, query: &ZendHashTable) -> Self {
+        Self {
+            query_params: Some(query.to_owned()),
+            ..self.clone()
+        }
+    }
+
+    pub fn get_uploaded_files(&self) -> ZBox<ZendHashTable> {
+        to_array(&self.uploaded_files)
+    }
+
+    pub fn with_uploaded_files(&self, uploaded_files: &ZendHashTable) -> PhpResult<Self> {
+        validate_uploaded_files(uploaded_files)
+            .map_err(|err| PhpException::new(err.into(), 0, invalid_argument_exception()))?;
+        Ok(Self {
+            uploaded_files: Some(uploaded_files.to_owned()),
+            ..self.clone()
+        })
+    }
+
+    pub fn get_parsed_body(&self) -> Zval {
+        self.parsed_body.shallow_clone()
+    }
+
+    pub fn with_parsed_body(&self, data: &Zval) -> PhpResult<Self> {
+        validate_parsed_body(data)
+            .map_err(|err| PhpException::new(err.into(), 0, invalid_argument_exception()))?;
+        Ok(Self {
+            parsed_body: data.shallow_clone(),
+            ..self.clone()
+        })
+    }
+
+    /// Fills the parsed body and uploaded files from a `multipart/form-data`
+    /// body, the way PHP fills `$_POST` and `$_FILES` for POST requests.
+    pub fn with_parsed_multipart_body(&self) -> PhpResult<Self> {
+        let runtime_error = |err: String| PhpException::new(err, 0, runtime_exception());
+        let boundary = multipart::boundary(&self.get_header_line("Content-Type"))
+            .ok_or_else(|| runtime_error("Request body is not multipart/form-data".into()))?;
+        let contents = self
+            .get_body()
+            .contents(max_body_size())
+            .map_err(|err| runtime_error(err.to_string()))?;
+        let form = multipart::parse_form_data(&contents, &boundary)
+            .map_err(|err| runtime_error(err.into()))?;
+        let mut parsed_body = Zval::new();
+        parsed_body.set_hashtable(QueryValue::entries_to_array(&form.fields)?);
+        Ok(Self {
+            uploaded_files: Some(FileTree::entries_to_array(&form.files)?),
+            parsed_body,
+            ..self.clone()
+        })
+    }
+
+    pub fn get_attributes(&self) -> ZBox<ZendHashTable> {
+        to_array(&self.attributes)
+    }
+
+    pub fn get_attribute(&self, name: &str, default: Option<&Zval>) -> Zval {
+        let attribute = self
+            .attributes
+            .as_ref()
+            .and_then(|attributes| attributes.get(name));
+        match attribute.or(default) {
+            Some(value) => value.shallow_clone(),
+            None => Zval::new(),
+        }
+    }
+
+    pub fn with_attribute(&self, name: &str, value: &Zval) -> PhpResult<Self> {
+        let mut request = self.clone();
+        request
+            .attributes
+            .get_or_insert_with(ZendHashTable::new)
+            .insert(name, value.shallow_clone())?;
+        Ok(request)
+    }
+
+    pub fn without_attribute(&self, name: &str) -> Self {
+        let mut request = self.clone();
+        if let Some(attributes) = request.attributes.as_mut() {
+            attributes.remove(name);
+        }
+        request
+    }
+
+    pub fn get_method(&self) -> String {
+        self.request.get_method()
+    }
+
+    pub fn with_method(&self, method: &str) -> PhpResult<Self> {
+        Ok(self.with_request(self.request.with_method(method)?))
+    }
+
+    pub fn get_request_target(&self) -> String {
+        self.request.get_request_target()
+    }
+
+    pub fn with_request_target(&self, request_target: &str) -> PhpResult<Self> {
+        Ok(self.with_request(self.request.with_request_target(request_target)?))
+    }
+
+    pub fn get_uri(&self) -> Uri {
+        self.request.get_uri()
+    }
+
+    pub fn get_absolute_uri(&self) -> Uri {
+        self.request.get_absolute_uri()
+    }
+
+    pub fn with_uri(&self, uri: &Uri, preserve_host: Option<bool>) -> PhpResult<Self> {
+        Ok(self.with_request(self.request.with_uri(uri, preserve_host)?))
+    }
+
+    pub fn get_protocol_version(&self) -> String {
+        self.request.get_protocol_version()
+    }
+
+    pub fn with_protocol_version(&self, version: &str) -> Self {
+        self.with_request(self.request.with_protocol_version(version))
+    }
+
+    pub fn get_headers(&self) -> PhpResult<ZBox<ZendHashTable>> {
+        self.request.get_headers()
+    }
+
+    pub fn count_headers(&self) -> usize {
+        self.request.count_headers()
+    }
+
+    pub fn get_iterator(&self) -> HeaderIterator {
+        self.request.get_iterator()
+    }
+
+    pub fn has_header(&self, name: &str) -> bool {
+        self.request.has_header(name)
+    }
+
+    pub fn get_header(&self, name: &str) -> Vec<String> {
+        self.request.get_header(name)
+    }
+
+    pub fn get_header_line(&self, name: &str) -> String {
+        self.request.get_header_line(name)
+    }
+
+    pub fn get_header_first_value(&self, name: &str, default: Option<String>) -> Option<String> {
+        self.request.get_header_first_value(name, default)
+    }
+
+    pub fn get_header_words(&self, name: &str) -> PhpResult<ZBox<ZendHashTable>> {
+        self.request.get_header_words(name)
+    }
+
+    pub fn with_header(&self, name: &str, value: &Zval) -> PhpResult<Self> {
+        Ok(self.with_request(self.request.with_header(name, value)?))
+    }
+
+    pub fn with_header_if_none(&self, name: &str, value: &Zval) -> PhpResult<Self> {
+        Ok(self.with_request(self.request.with_header_if_none(name, value)?))
+    }
+
+    pub fn with_headers(&self, headers: &ZendHashTable) -> PhpResult<Self> {
+        Ok(self.with_request(self.request.with_headers(headers)?))
+    }
+
+    pub fn with_added_header(&self, name: &str, value: &Zval) -> PhpResult<Self> {
+        Ok(self.with_request(self.request.with_added_header(name, value)?))
+    }
+
+    pub fn without_header(&self, name: &str) -> Self {
+        self.with_request(self.request.without_header(name))
+    }
+
+    pub fn without_header_matching(&self, prefix: &str) -> Self {
+        self.with_request(self.request.without_header_matching(prefix))
+    }
+
+    pub fn get_body(&self) -> Stream {
+        self.request.get_body()
+    }
+
+    pub fn get_body_contents(&self) -> PhpResult<Binary<u8>> {
+        self.request.get_body_contents()
+    }
+
+    pub fn with_body(&self, body: &Stream) -> Self {
+        self.with_request(self.request.with_body(body))
+    }
+
+    pub fn with_cloned_body(&self) -> PhpResult<Self> {
+        Ok(self.with_request(self.request.with_cloned_body()?))
+    }
+
+    pub fn to_raw_message(&self) -> PhpResult<Binary<u8>> {
+        self.request.to_raw_message()
+    }
+
+    #[rename("__debugInfo")]
+    pub fn debug_info(&self) -> PhpResult<ZBox<ZendHashTable>> {
+        let mut info = self.request.debug_info()?;
+        info.insert("serverParams", self.get_server_params())?;
+        info.insert("cookieParams", self.get_cookie_params())?;
+        info.insert("queryParams", self.get_query_params())?;
+        info.insert("uploadedFiles", self.get_uploaded_files())?;
+        info.insert("parsedBody", self.get_parsed_body())?;
+        info.insert("attributes", self.get_attributes())?;
+        Ok(info)
+    }
+
+    #[rename("__serialize")]
+    pub fn serialize(&self) -> PhpResult<ZBox<ZendHashTable>> {
+        let mut data = ZendHashTable::new();
+        self.request.serialize_into(&mut data)?;
+        data.insert("serverParams", self.get_server_params())?;
+        data.insert("cookieParams", self.get_cookie_params())?;
+        data.insert("queryParams", self.get_query_params())?;
+        data.insert("uploadedFiles", self.get_uploaded_files())?;
+        data.insert("parsedBody", self.get_parsed_body())?;
+        data.insert("attributes", self.get_attributes())?;
+        Ok(data)
+    }
+
+    #[rename("__unserialize")]
+    pub fn unserialize(
+        #[this] this: &mut ZendClassObject<Self>,
+        data: &ZendHashTable,
+    ) -> PhpResult<()> {
+        this.initialize(Self::from_serialized(data)?);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_request(method: &str, uri: &str) -> ServerRequest {
+        let message = Message::new(HeaderMap::new(), None, None);
+        let request = Request::new(method, Uri::new(uri).unwrap(), message).unwrap();
+        ServerRequest::new(request, None)
+    }
+
+    #[test]
+    fn parse_raw_get_request() {
+        let request = parse_raw_request(
+            b"GET /search?q=rust HTTP/1.0\r\nHost: example.com:8080\r\nAccept: text/html\r\nAccept: */*\r\n\r\n",
+        )
+        .unwrap();
+        assert_eq!(request.get_method(), "GET");
+        assert_eq!(request.get_request_target(), "/search?q=rust");
+        assert_eq!(
+            request.get_uri().to_string(),
+            "http://example.com:8080/search?q=rust"
+        );
+        assert_eq!(request.get_protocol_version(), "1.0");
+        assert_eq!(request.get_header("accept"), ["text/html", "*/*"]);
+        assert_eq!(request.get_body().get_size(), Some(0));
+    }
+
+    #[test]
+    fn parse_raw_post_request() {
+        let request =
+            parse_raw_request(b"POST /items HTTP/1.1\nContent-Length: 9\n\nname=a\r\nb").unwrap();
+        assert_eq!(request.get_method(), "POST");
+        assert_eq!(request.get_uri().to_string(), "/items");
+        assert_eq!(request.get_header_line("Content-Length"), "9");
+        assert_eq!(*request.get_body().to_string(), b"name=a\r\nb");
+
+        let request =
+            parse_raw_request(b"OPTIONS * HTTP/1.1\r\nHost: example.com\r\n\r\n").unwrap();
+        assert_eq!(request.get_request_target(), "*");
+        assert_eq!(request.get_uri().to_string(), "http://example.com");
+
+        let request =
+            parse_raw_request(b"GET http://example.org/a HTTP/1.1\r\nHost: proxy\r\n\r\n").unwrap();
+        assert_eq!(request.get_uri().to_string(), "http://example.org/a");
+    }
+
+    #[test]
+    fn parse_raw_malformed() {
+        let cases: [&[u8]; 6] = [
+            b"GET / HTTP/1.1\r\nHost: example.com\r\n",
+            b"GET /\r\n\r\n",
+            b"GET / HTTP/1.1 extra\r\n\r\n",
+            b"GET / FTP/1.1\r\n\r\n",
+            b"GET / HTTP/1.1\r\nno colon\r\n\r\n",
+            b"GET / HTTP/1.1\r\nBad Name: 1\r\n\r\n",
+        ];
+        for raw in cases {
+            assert!(parse_raw_request(raw).is_err(), "raw: {raw:?}");
+        }
+    }
+
+    #[test]
+    fn delegates_to_request() {
+        let request = new_request("GET", "http://example.com/a?b");
+        assert_eq!(request.get_method(), "GET");
+        assert_eq!(request.get_request_target(), "/a?b");
+        assert_eq!(request.get_uri().to_string(), "http://example.com/a?b");
+        assert_eq!(request.get_header_line("Host"), "example.com");
+        assert_eq!(request.get_protocol_version(), "1.1");
+
+        let request = request
+            .with_method("POST")
+            .unwrap()
+            .with_request_target("*")
+            .unwrap()
+            .with_protocol_version("2");
+        assert_eq!(request.get_method(), "POST");
+        assert_eq!(request.get_request_target(), "*");
+        assert_eq!(request.get_protocol_version(), "2");
+
+        let uri = Uri::new("https://example.org/c").unwrap();
+        let request = request.with_uri(&uri, None).unwrap();
+        assert_eq!(request.get_header_line("Host"), "example.org");
+        assert!(!request.without_header("Host").has_header("Host"));
+
+        let body = Stream::from_bytes(b"body".to_vec());
+        let request = request.with_body(&body);
+        assert_eq!(*request.get_body().to_string(), b"body");
+    }
+
+    #[test]
+    fn with_request_keeps_server_state() {
+        let request = new_request("GET", "/")
+            .with_parsed_body(&Zval::new())
+            .unwrap()
+            .with_method("PUT")
+            .unwrap();
+        assert!(request.get_parsed_body().is_null());
+        assert!(request.server_params.is_none());
+    }
+
+    #[test]
+    fn with_parsed_body_validation() {
+        let request = new_request("POST", "/");
+        assert!(request.with_parsed_body(&Zval::new()).is_ok());
+        let mut value = Zval::new();
+        value.set_long(1);
+        assert!(validate_parsed_body(&value).is_err());
+        value.set_bool(true);
+        assert!(validate_parsed_body(&value).is_err());
+        value.set_double(1.5);
+        assert!(validate_parsed_body(&value).is_err());
+    }
+}
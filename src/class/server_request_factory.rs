@@ -0,0 +1,495 @@
+use crate::class::request::Request;
+use crate::class::server_request::ServerRequest;
+use crate::class::stream::Stream;
+use crate::class::uploaded_file::UploadedFile;
+use crate::class::uri::{split_authority, ParseMode, Uri};
+use crate::class::uri_factory::uri_argument;
+use crate::header_map::HeaderMap;
+use crate::message::Message;
+use crate::util::{invalid_argument_exception, is_valid_component, runtime_exception};
+use ext_php_rs::boxed::ZBox;
+use ext_php_rs::convert::IntoZval;
+use ext_php_rs::prelude::*;
+use ext_php_rs::types::{ArrayKey, ZendHashTable, Zval};
+use ext_php_rs::zend::ProcessGlobals;
+use std::net::Ipv6Addr;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// The most bytes a request body is buffered up to, zero meaning no limit.
+static MAX_BODY_SIZE: AtomicU64 = AtomicU64::new(0);
+
+pub(crate) fn max_body_size() -> u64 {
+    MAX_BODY_SIZE.load(Ordering::Relaxed)
+}
+
+// PSR-17 ServerRequestFactoryInterface.
+#[php_class(name = "Takaram\\Psr7\\Internal\\ServerRequestFactory")]
+pub struct ServerRequestFactory;
+
+#[php_impl]
+impl ServerRequestFactory {
+    pub fn __construct() -> Self {
+        Self
+    }
+
+    /// Caps the body `fromGlobals()` and the body parsers read into memory;
+    /// larger bodies throw a RuntimeException. Zero removes the limit.
+    pub fn set_max_body_size(size: i64) -> PhpResult<()> {
+        let size = u64::try_from(size).map_err(|_| {
+            PhpException::new(
+                "Maximum body size must be non-negative".into(),
+                0,
+                invalid_argument_exception(),
+            )
+        })?;
+        MAX_BODY_SIZE.store(size, Ordering::Relaxed);
+        Ok(())
+    }
+
+    pub fn get_max_body_size() -> u64 {
+        max_body_size()
+    }
+
+    pub fn create_server_request(
+        &self,
+        method: &str,
+        uri: &Zval,
+        server_params: Option<&ZendHashTable>,
+    ) -> PhpResult<ServerRequest> {
+        let message = Message::new(HeaderMap::new(), None, None);
+        let request = Request::new(method, uri_argument(uri)?, message)
+            .map_err(|err| PhpException::new(err.into(), 0, invalid_argument_exception()))?;
+        Ok(ServerRequest::new(
+            request,
+            server_params.map(ToOwned::to_owned),
+        ))
+    }
+
+    /// Rebuilds the request URI from `$_SERVER`-style parameters. With
+    /// `trustForwarded`, `X-Forwarded-Proto` and `X-Forwarded-Host` take
+    /// precedence. Malformed values are skipped rather than thrown on.
+    pub fn uri_from_server_params(server: &ZendHashTable, trust_forwarded: Option<bool>) -> Uri {
+        uri_from_server_params(
+            |name| server.get(name).and_then(Zval::string),
+            trust_forwarded.unwrap_or(false),
+        )
+    }
+
+    /// Builds the current request from `$_SERVER`, `$_GET`, `$_POST`,
+    /// `$_COOKIE`, `$_FILES` and `php://input`.
+    pub fn from_globals() -> PhpResult<ServerRequest> {
+        let body = read_input()?;
+        let globals = ProcessGlobals::get();
+        let server = globals
+            .http_server_vars()
+            .map_or_else(ZendHashTable::new, ToOwned::to_owned);
+        let param = |name: &str| server.get(name).and_then(Zval::string);
+
+        let uri = uri_from_server_params(param, false);
+        let method = param("REQUEST_METHOD").unwrap_or_else(|| "GET".to_string());
+        let headers = headers_from_server_params(
+            server
+                .iter()
+                .filter_map(|(key, value)| Some((key.to_string(), value.string()?))),
+        );
+        let version = param("SERVER_PROTOCOL")
+            .and_then(|protocol| protocol.strip_prefix("HTTP/").map(str::to_string));
+        let message = Message::new(headers, Some(body), version);
+        let request = Request::new(&method, uri, message)
+            .map_err(|err| PhpException::new(err.into(), 0, invalid_argument_exception()))?;
+
+        // PHP only fills `$_POST` for form submissions.
+        let mut parsed_body = Zval::new();
+        if method == "POST" && is_form(&request.get_header_line("Content-Type")) {
+            parsed_body.set_hashtable(globals.http_post_vars().to_owned());
+        }
+        let uploaded_files = uploaded_files(globals.http_files_vars())?;
+
+        ServerRequest::new(request, Some(server))
+            .with_cookie_params(globals.http_cookie_vars())
+            .with_query_params(globals.http_get_vars())
+            .with_uploaded_files(&uploaded_files)?
+            .with_parsed_body(&parsed_body)
+    }
+}
+
+fn read_input() -> PhpResult<Stream> {
+    let contents = Stream::fopen("php://input", "rb")?
+        .contents(max_body_size())
+        .map_err(|err| PhpException::new(err.to_string(), 0, runtime_exception()))?;
+    Ok(Stream::from_bytes(contents))
+}
+
+// Rebuilds the request URI the way guzzle's `getUriFromGlobals()` does.
+// The host comes from the Host header, falling back to the server name
+// and address; a value that is not a valid host with an optional port is
+// skipped. `X-Forwarded-Proto` and `X-Forwarded-Host` are only used when
+// the caller trusts the proxy in front. Input that does not make a valid
+// URI degrades to its scheme and authority, then to an empty URI.
+fn uri_from_server_params(param: impl Fn(&str) -> Option<String>, trust_forwarded: bool) -> Uri {
+    // Proxies list one value per hop; the first is the client's.
+    let forwarded = |name| {
+        param(name)
+            .filter(|_| trust_forwarded)
+            .and_then(|value| Some(value.split(',').next()?.trim().to_string()))
+    };
+    let forwarded_scheme = forwarded("HTTP_X_FORWARDED_PROTO")
+        .map(|proto| proto.to_ascii_lowercase())
+        .filter(|proto| proto == "http" || proto == "https");
+    let forwarded_host = forwarded("HTTP_X_FORWARDED_HOST").and_then(|host| host_authority(&host));
+    // SERVER_PORT is the port behind the proxy, so it only applies to a
+    // request that reached the server directly.
+    let direct = forwarded_scheme.is_none() && forwarded_host.is_none();
+
+    let scheme = forwarded_scheme.unwrap_or_else(|| {
+        let https = param("HTTPS").is_some_and(|https| !https.is_empty() && https != "off");
+        if https { "https" } else { "http" }.to_string()
+    });
+    let (mut authority, has_port) = forwarded_host
+        .or_else(|| {
+            ["HTTP_HOST", "SERVER_NAME", "SERVER_ADDR"]
+                .into_iter()
+                .filter_map(&param)
+                .find_map(|host| host_authority(&host))
+        })
+        .unwrap_or_default();
+    if direct && !authority.is_empty() && !has_port {
+        if let Some(port) = param("SERVER_PORT").filter(|port| port.parse::<u16>().is_ok()) {
+            authority.push(':');
+            authority.push_str(&port);
+        }
+    }
+
+    // Without REQUEST_URI (CLI, some CGI setups), the script path and
+    // QUERY_STRING stand in for it.
+    let request_uri = param("REQUEST_URI")
+        .filter(|request_uri| !request_uri.is_empty())
+        .or_else(|| param("PHP_SELF"))
+        .unwrap_or_default();
+    // An absolute-form request target (sent to proxies) carries its own
+    // scheme and authority; only its path and query are used.
+    let target = match request_uri.split_once("://") {
+        Some((_, rest)) if !request_uri.starts_with('/') => {
+            rest.find('/').map_or("", |start| &rest[start..])
+        }
+        _ => &request_uri,
+    };
+    let (path, query) = match target.split_once('?') {
+        Some((path, query)) => (path, query.to_string()),
+        None => (target, param("QUERY_STRING").unwrap_or_default()),
+    };
+    let mut uri = format!("{scheme}://{authority}{path}");
+    if !query.is_empty() {
+        uri.push('?');
+        uri.push_str(&query);
+    }
+    Uri::new_with_mode(uri, ParseMode::Lenient)
+        .or_else(|_| Uri::new_with_mode(format!("{scheme}://{authority}"), ParseMode::Lenient))
+        .unwrap_or_default()
+}
+
+// `host` as an authority, along with whether it has a port. A bare IPv6
+// server address is put in brackets.
+pub(crate) fn host_authority(host: &str) -> Option<(String, bool)> {
+    let host = match host.parse::<Ipv6Addr>() {
+        Ok(_) => format!("[{host}]"),
+        Err(_) => host.to_string(),
+    };
+    let (user_info, name, port) = split_authority(&host)?;
+    let valid = user_info.is_empty()
+        && !name.is_empty()
+        && (name.starts_with('[') || is_valid_component(name, b""));
+    valid.then_some((host, port.is_some()))
+}
+
+fn headers_from_server_params(params: impl Iterator<Item = (String, String)>) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    let mut redirect_authorization = None;
+    for (key, value) in params {
+        if key == "REDIRECT_HTTP_AUTHORIZATION" {
+            redirect_authorization = Some(value);
+        } else if let Some(name) = header_name(&key) {
+            // Values PHP let through but HeaderMap rejects are dropped.
+            let _ = headers.append(&name, vec![value]);
+        }
+    }
+    // Some servers only pass the Authorization header on after a rewrite.
+    if let Some(value) = redirect_authorization {
+        if !headers.contains("Authorization") {
+            let _ = headers.append("Authorization", vec![value]);
+        }
+    }
+    headers
+}
+
+// `HTTP_ACCEPT_LANGUAGE` becomes `Accept-Language`; the content headers
+// are the only ones PHP passes without the `HTTP_` prefix.
+fn header_name(key: &str) -> Option<String> {
+    let name = match key.strip_prefix("HTTP_") {
+        Some(name) => name,
+        None if matches!(key, "CONTENT_TYPE" | "CONTENT_LENGTH" | "CONTENT_MD5") => key,
+        None => return None,
+    };
+    let words: Vec<String> = name
+        .split('_')
+        .map(|word| {
+            let word = word.to_ascii_lowercase();
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+                None => String::new(),
+            }
+        })
+        .collect();
+    Some(words.join("-"))
+}
+
+fn is_form(content_type: &str) -> bool {
+    let media_type = content_type.split(';').next().unwrap_or("").trim();
+    media_type.eq_ignore_ascii_case("application/x-www-form-urlencoded")
+        || media_type.eq_ignore_ascii_case("multipart/form-data")
+}
+
+// Turns `$_FILES` into a tree of UploadedFile objects. For a field named
+// like `doc[]`, PHP puts each attribute (`tmp_name`, `size`, ...) in its
+// own nested array, which is transposed back here.
+fn uploaded_files(files: &ZendHashTable) -> PhpResult<ZBox<ZendHashTable>> {
+    let mut tree = ZendHashTable::new();
+    for (key, spec) in files.iter() {
+        if let Some(spec) = spec.array() {
+            insert(&mut tree, &key, uploaded_file_tree(spec)?)?;
+        }
+    }
+    Ok(tree)
+}
+
+fn uploaded_file_tree(spec: &ZendHashTable) -> PhpResult<Zval> {
+    let tmp_names = match spec.get("tmp_name").and_then(Zval::array) {
+        Some(tmp_names) => tmp_names,
+        None => {
+            let string = |name| spec.get(name).and_then(Zval::string);
+            let long = |name| spec.get(name).and_then(Zval::long);
+            let file = UploadedFile::__construct(
+                string("tmp_name").unwrap_or_default(),
+                long("size"),
+                long("error").unwrap_or(0),
+                string("name"),
+                string("type"),
+            )?;
+            return Ok(file.into_zval(false)?);
+        }
+    };
+    let mut tree = ZendHashTable::new();
+    for (key, tmp_name) in tmp_names.iter() {
+        let mut child = ZendHashTable::new();
+        child.insert("tmp_name", tmp_name.shallow_clone())?;
+        for name in ["size", "error", "name", "type"] {
+            let value = spec
+                .get(name)
+                .and_then(Zval::array)
+                .and_then(|values| get(values, &key));
+            if let Some(value) = value {
+                child.insert(name, value.shallow_clone())?;
+            }
+        }
+        insert(&mut tree, &key, uploaded_file_tree(&child)?)?;
+    }
+    Ok(tree.into_zval(false)?)
+}
+
+fn get<'a>(table: &'a ZendHashTable, key: &ArrayKey) -> Option<&'a Zval> {
+    match key {
+        ArrayKey::Long(index) => table.get_index(u64::try_from(*index).ok()?),
+        ArrayKey::String(key) => table.get(key),
+    }
+}
+
+fn insert(table: &mut ZendHashTable, key: &ArrayKey, value: Zval) -> PhpResult<()> {
+    match key {
+        ArrayKey::Long(index) => match u64::try_from(*index) {
+            Ok(index) => table.insert_at_index(index, value)?,
+            Err(_) => table.insert(&index.to_string(), value)?,
+        },
+        ArrayKey::String(key) => table.insert(key, value)?,
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn uri_from(params: &[(&str, &str)]) -> String {
+        uri_from_trusted(params, false)
+    }
+
+    fn uri_from_trusted(params: &[(&str, &str)], trust_forwarded: bool) -> String {
+        let params: HashMap<_, _> = params.iter().copied().collect();
+        super::uri_from_server_params(
+            |name| params.get(name).map(|value| value.to_string()),
+            trust_forwarded,
+        )
+        .to_string()
+    }
+
+    #[test]
+    fn uri_from_server_params() {
+        let cases: [(&[(&str, &str)], &str); 9] = [
+            (
+                &[
+                    ("HTTPS", "on"),
+                    ("HTTP_HOST", "example.com:8443"),
+                    ("SERVER_PORT", "443"),
+                    ("REQUEST_URI", "/a b?q=1"),
+                ],
+                "https://example.com:8443/a%20b?q=1",
+            ),
+            (
+                &[
+                    ("HTTPS", "off"),
+                    ("SERVER_NAME", "example.com"),
+                    ("SERVER_PORT", "8080"),
+                    ("REQUEST_URI", "/path"),
+                    ("QUERY_STRING", "x=y"),
+                ],
+                "http://example.com:8080/path?x=y",
+            ),
+            (
+                &[
+                    ("HTTP_HOST", "[::1]"),
+                    ("SERVER_PORT", "8080"),
+                    ("REQUEST_URI", "/"),
+                ],
+                "http://[::1]:8080/",
+            ),
+            (
+                &[("SERVER_ADDR", "::1"), ("REQUEST_URI", "/")],
+                "http://[::1]/",
+            ),
+            (
+                &[
+                    ("HTTP_HOST", "evil.com/x?"),
+                    ("SERVER_NAME", "example.com"),
+                    ("REQUEST_URI", "/"),
+                ],
+                "http://example.com/",
+            ),
+            (
+                &[
+                    ("HTTP_HOST", "example.com"),
+                    ("REQUEST_URI", "http://proxy.example/p?q"),
+                ],
+                "http://example.com/p?q",
+            ),
+            (
+                &[
+                    ("HTTP_HOST", "example.com:99999"),
+                    ("SERVER_NAME", "example.org"),
+                    ("SERVER_PORT", "8080"),
+                    ("REQUEST_URI", "/"),
+                ],
+                "http://example.org:8080/",
+            ),
+            (
+                &[
+                    ("HTTP_HOST", "a:b"),
+                    ("SERVER_NAME", "example.org"),
+                    ("REQUEST_URI", "/"),
+                ],
+                "http://example.org/",
+            ),
+            (
+                &[
+                    ("HTTP_HOST", "user@example.com"),
+                    ("SERVER_ADDR", "192.0.2.1"),
+                    ("REQUEST_URI", "/"),
+                ],
+                "http://192.0.2.1/",
+            ),
+        ];
+        for (params, expected) in cases {
+            assert_eq!(uri_from(params), expected, "params: {params:?}");
+        }
+    }
+
+    #[test]
+    fn uri_from_server_params_without_request_uri() {
+        let params = [
+            ("HTTP_HOST", "example.com"),
+            ("PHP_SELF", "/index.php"),
+            ("QUERY_STRING", "a=1"),
+        ];
+        assert_eq!(uri_from(&params), "http://example.com/index.php?a=1");
+        let params = [("HTTP_HOST", "example.com"), ("REQUEST_URI", "")];
+        assert_eq!(uri_from(&params), "http://example.com");
+        assert_eq!(uri_from(&[]), "http:");
+    }
+
+    #[test]
+    fn uri_from_forwarded_params() {
+        let params = [
+            ("HTTP_HOST", "backend:8080"),
+            ("SERVER_PORT", "8080"),
+            ("HTTP_X_FORWARDED_PROTO", "HTTPS, http"),
+            ("HTTP_X_FORWARDED_HOST", "example.com, proxy.internal"),
+            ("REQUEST_URI", "/a"),
+        ];
+        assert_eq!(uri_from_trusted(&params, true), "https://example.com/a");
+        assert_eq!(uri_from(&params), "http://backend:8080/a");
+
+        let params = [
+            ("SERVER_NAME", "example.com"),
+            ("SERVER_PORT", "8080"),
+            ("HTTP_X_FORWARDED_PROTO", "https"),
+            ("REQUEST_URI", "/"),
+        ];
+        assert_eq!(uri_from_trusted(&params, true), "https://example.com/");
+
+        let params = [
+            ("HTTPS", "on"),
+            ("HTTP_HOST", "example.com"),
+            ("HTTP_X_FORWARDED_PROTO", "gopher"),
+            ("HTTP_X_FORWARDED_HOST", "bad host"),
+            ("REQUEST_URI", "/"),
+        ];
+        assert_eq!(uri_from_trusted(&params, true), "https://example.com/");
+    }
+
+    #[test]
+    fn header_names() {
+        assert_eq!(
+            header_name("HTTP_ACCEPT_LANGUAGE").as_deref(),
+            Some("Accept-Language")
+        );
+        assert_eq!(
+            header_name("HTTP_X_FORWARDED_FOR").as_deref(),
+            Some("X-Forwarded-For")
+        );
+        assert_eq!(header_name("CONTENT_TYPE").as_deref(), Some("Content-Type"));
+        assert_eq!(header_name("REQUEST_METHOD"), None);
+    }
+
+    #[test]
+    fn headers_from_server_params() {
+        let params = [
+            ("HTTP_HOST", "example.com"),
+            ("CONTENT_LENGTH", "3"),
+            ("REDIRECT_HTTP_AUTHORIZATION", "Bearer token"),
+            ("SERVER_NAME", "example.com"),
+        ]
+        .map(|(key, value)| (key.to_string(), value.to_string()));
+        let headers = super::headers_from_server_params(params.into_iter());
+        assert_eq!(headers.line("host"), "example.com");
+        assert_eq!(headers.line("content-length"), "3");
+        assert_eq!(headers.line("authorization"), "Bearer token");
+        assert!(!headers.contains("Server-Name"));
+    }
+
+    #[test]
+    fn is_form() {
+        assert!(super::is_form("application/x-www-form-urlencoded"));
+        assert!(super::is_form("multipart/form-data; boundary=abc"));
+        assert!(!super::is_form("application/json"));
+    }
+}
@@ -0,0 +1,1155 @@
+use crate::util::{
+    base64_decode, base64_encode, invalid_argument_exception, invalid_serialized_data,
+    stream_exception,
+};
+use ext_php_rs::binary::Binary;
+use ext_php_rs::boxed::ZBox;
+use ext_php_rs::prelude::*;
+use ext_php_rs::types::{ZendCallable, ZendClassObject, ZendHashTable, Zval};
+use php_resource::{call, PhpResource};
+use std::cell::{RefCell, RefMut};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Cursor, Read, Seek, SeekFrom, Write};
+use std::rc::Rc;
+
+mod php_resource;
+
+const SEEK_SET: i64 = 0;
+const SEEK_CUR: i64 = 1;
+const SEEK_END: i64 = 2;
+
+trait Io: Read + Write + Seek {}
+
+impl<T: Read + Write + Seek> Io for T {}
+
+enum Resource {
+    Memory(Cursor<Vec<u8>>),
+    File(File),
+    Php(PhpResource),
+}
+
+impl Resource {
+    fn io(&mut self) -> &mut dyn Io {
+        match self {
+            Self::Memory(cursor) => cursor,
+            Self::File(file) => file,
+            Self::Php(resource) => resource,
+        }
+    }
+
+    fn size(&self) -> io::Result<u64> {
+        match self {
+            Self::Memory(cursor) => Ok(cursor.get_ref().len() as u64),
+            Self::File(file) => Ok(file.metadata()?.len()),
+            Self::Php(resource) => resource.size(),
+        }
+    }
+
+    // Growing fills the new bytes with zeros, as `ftruncate()` does.
+    fn set_len(&mut self, size: u64) -> io::Result<()> {
+        match self {
+            Self::Memory(cursor) => {
+                let size = usize::try_from(size).map_err(io::Error::other)?;
+                cursor.get_mut().resize(size, 0);
+                Ok(())
+            }
+            Self::File(file) => file.set_len(size),
+            Self::Php(resource) => resource.truncate(size),
+        }
+    }
+
+    fn eof(&mut self) -> io::Result<bool> {
+        if let Self::Php(resource) = self {
+            return resource.eof();
+        }
+        let size = self.size()?;
+        Ok(self.io().stream_position()? >= size)
+    }
+
+    // Bytes left before the end, when the size and position are known.
+    fn remaining(&mut self) -> Option<u64> {
+        let size = self.size().ok()?;
+        let pos = self.io().stream_position().ok()?;
+        Some(size.saturating_sub(pos))
+    }
+}
+
+struct Inner {
+    // `None` once the stream is closed or detached.
+    resource: Option<Resource>,
+    readable: bool,
+    writable: bool,
+    seekable: bool,
+    mode: String,
+    uri: String,
+}
+
+impl Inner {
+    fn resource(&mut self) -> io::Result<&mut Resource> {
+        self.resource
+            .as_mut()
+            .ok_or_else(|| io::Error::other("Stream is detached"))
+    }
+
+    fn io(&mut self) -> io::Result<&mut dyn Io> {
+        Ok(self.resource()?.io())
+    }
+
+    fn detach(&mut self) -> Option<Resource> {
+        self.readable = false;
+        self.writable = false;
+        self.seekable = false;
+        self.resource.take()
+    }
+}
+
+// What `getMetadata()` reports, named as by `stream_get_meta_data()`.
+#[derive(Debug, PartialEq)]
+struct Metadata {
+    eof: bool,
+    wrapper_type: &'static str,
+    stream_type: &'static str,
+    mode: String,
+    seekable: bool,
+    uri: String,
+}
+
+// Messages share their body with every instance derived from them through
+// `with*` methods, so the underlying resource is reference counted.
+#[php_class(name = "Takaram\\Psr7\\Internal\\Stream")]
+#[derive(Clone)]
+pub struct Stream {
+    inner: Rc<RefCell<Inner>>,
+}
+
+// An empty memory stream.
+impl Default for Stream {
+    fn default() -> Self {
+        Self::from_bytes(Vec::new())
+    }
+}
+
+impl Stream {
+    pub fn from_bytes(bytes: Vec<u8>) -> Self {
+        Self::from_memory(Cursor::new(bytes))
+    }
+
+    pub fn open(path: &str, mode: &str) -> Result<Self, String> {
+        let (options, readable, writable) =
+            open_options(mode).ok_or_else(|| format!("Invalid stream mode: {mode}"))?;
+        let file = options
+            .open(path)
+            .map_err(|err| format!("Failed to open {path}: {err}"))?;
+        // Pipes and character devices cannot seek.
+        let seekable = file.metadata().is_ok_and(|metadata| metadata.is_file());
+        Ok(Self::from_inner(Inner {
+            resource: Some(Resource::File(file)),
+            readable,
+            writable,
+            seekable,
+            mode: mode.to_string(),
+            uri: path.to_string(),
+        }))
+    }
+
+    pub(crate) fn is_valid_mode(mode: &str) -> bool {
+        open_options(mode).is_some()
+    }
+
+    /// Opens `path` with PHP's `fopen()`, so stream wrappers such as
+    /// `php://temp` work and `open_basedir` applies.
+    pub(crate) fn fopen(path: &str, mode: &str) -> PhpResult<Self> {
+        if !Self::is_valid_mode(mode) {
+            return Err(PhpException::new(
+                format!("Invalid stream mode: {mode}"),
+                0,
+                invalid_argument_exception(),
+            ));
+        }
+        let resource = ZendCallable::try_from_name("fopen")?.try_call(vec![&path, &mode])?;
+        if !resource.is_resource() {
+            return Err(PhpException::new(
+                format!("Failed to open {path}"),
+                0,
+                stream_exception(),
+            ));
+        }
+        Self::from_php_resource(&resource)
+    }
+
+    /// Wraps a PHP stream resource without copying it; reads and writes go
+    /// to the resource, and its mode and seekability are kept.
+    pub(crate) fn from_php_resource(resource: &Zval) -> PhpResult<Self> {
+        if !resource.is_resource() {
+            return Err(PhpException::new(
+                "Argument must be a stream resource".into(),
+                0,
+                invalid_argument_exception(),
+            ));
+        }
+        let resource = PhpResource::new(resource);
+        let meta_data = resource.meta_data().map_err(runtime_error)?;
+        let meta_data = meta_data.array().ok_or_else(|| {
+            PhpException::new(
+                "Argument must be a stream resource".into(),
+                0,
+                invalid_argument_exception(),
+            )
+        })?;
+        let string = |key| {
+            meta_data
+                .get(key)
+                .and_then(Zval::string)
+                .unwrap_or_default()
+        };
+        let mode = string("mode");
+        let (readable, writable) = php_resource::access(&mode);
+        Ok(Self::from_inner(Inner {
+            readable,
+            writable,
+            seekable: meta_data.get("seekable").and_then(Zval::bool) == Some(true),
+            uri: string("uri"),
+            mode,
+            resource: Some(Resource::Php(resource)),
+        }))
+    }
+
+    fn from_memory(cursor: Cursor<Vec<u8>>) -> Self {
+        Self::from_inner(Inner {
+            resource: Some(Resource::Memory(cursor)),
+            readable: true,
+            writable: true,
+            seekable: true,
+            mode: "w+b".to_string(),
+            uri: "php://memory".to_string(),
+        })
+    }
+
+    fn from_inner(inner: Inner) -> Self {
+        Self {
+            inner: Rc::new(RefCell::new(inner)),
+        }
+    }
+
+    fn lock(&self) -> RefMut<'_, Inner> {
+        self.inner.borrow_mut()
+    }
+
+    pub(crate) fn copy_to(&self, writer: &mut impl Write) -> io::Result<u64> {
+        let mut inner = self.lock();
+        if !inner.readable {
+            return Err(unsupported("Stream is not readable"));
+        }
+        let io = inner.io()?;
+        io.rewind()?;
+        io::copy(io, writer)
+    }
+
+    fn _read(&self, length: usize) -> io::Result<Vec<u8>> {
+        let mut inner = self.lock();
+        if !inner.readable {
+            return Err(unsupported("Stream is not readable"));
+        }
+        let resource = inner.resource()?;
+        // `length` comes from the caller, so only what is left is reserved;
+        // `take` bounds the read itself.
+        let capacity = resource.remaining().unwrap_or(0).min(length as u64);
+        let mut buf = Vec::with_capacity(capacity as usize);
+        resource.io().take(length as u64).read_to_end(&mut buf)?;
+        Ok(buf)
+    }
+
+    // Up to and including the next `\n`, so a `\r\n` ending is kept whole.
+    // Bytes are read one at a time to leave the cursor right after the line.
+    fn _read_line(&self, max_length: Option<usize>) -> io::Result<Vec<u8>> {
+        let mut inner = self.lock();
+        if !inner.readable {
+            return Err(unsupported("Stream is not readable"));
+        }
+        let io = inner.io()?;
+        let mut line = Vec::new();
+        let mut byte = [0];
+        while max_length.is_none_or(|max| line.len() < max) && io.read(&mut byte)? == 1 {
+            line.push(byte[0]);
+            if byte[0] == b'\n' {
+                break;
+            }
+        }
+        Ok(line)
+    }
+
+    // Reads from `offset` and puts the cursor back where it was, even when
+    // the read fails.
+    fn _read_at(&self, offset: u64, length: usize) -> io::Result<Vec<u8>> {
+        let mut inner = self.lock();
+        if !inner.readable {
+            return Err(unsupported("Stream is not readable"));
+        }
+        if !inner.seekable {
+            return Err(unsupported("Stream is not seekable"));
+        }
+        let io = inner.io()?;
+        let pos = io.stream_position()?;
+        io.seek(SeekFrom::Start(offset))?;
+        let mut buf = Vec::new();
+        let read = io.take(length as u64).read_to_end(&mut buf);
+        io.seek(SeekFrom::Start(pos))?;
+        read.map(|_| buf)
+    }
+
+    // The inclusive byte range `start..=end`, with `end` defaulting to the
+    // last byte, as in an HTTP Range header.
+    fn _get_range(&self, start: u64, end: Option<u64>) -> io::Result<Vec<u8>> {
+        let size = self
+            .get_size()
+            .ok_or_else(|| io::Error::other("Stream size is unknown"))? as u64;
+        let end = end.unwrap_or(size.saturating_sub(1));
+        if start > end {
+            return Err(invalid_input("Range start must not be after its end"));
+        }
+        if end >= size {
+            return Err(invalid_input("Range is out of the stream's bounds"));
+        }
+        let length = usize::try_from(end - start + 1).map_err(io::Error::other)?;
+        self._read_at(start, length)
+    }
+
+    // Hands what is left to `write` in chunks of up to `chunk_size` bytes,
+    // so a large body never sits in memory at once.
+    fn _pipe(
+        &self,
+        chunk_size: usize,
+        mut write: impl FnMut(&[u8]) -> io::Result<()>,
+    ) -> io::Result<u64> {
+        let mut written = 0;
+        loop {
+            let chunk = self._read(chunk_size)?;
+            if chunk.is_empty() {
+                return Ok(written);
+            }
+            write(&chunk)?;
+            written += chunk.len() as u64;
+        }
+    }
+
+    fn _write(&self, bytes: &[u8]) -> io::Result<usize> {
+        let mut inner = self.lock();
+        if !inner.writable {
+            return Err(unsupported("Stream is not writable"));
+        }
+        inner.io()?.write_all(bytes)?;
+        Ok(bytes.len())
+    }
+
+    // Resizes to `size` bytes and moves the cursor back inside if it was
+    // past the new end.
+    fn _truncate(&self, size: u64) -> io::Result<()> {
+        let mut inner = self.lock();
+        if !inner.writable {
+            return Err(unsupported("Stream is not writable"));
+        }
+        let resource = inner.resource()?;
+        resource.set_len(size)?;
+        let io = resource.io();
+        if io.stream_position()? > size {
+            io.seek(SeekFrom::Start(size))?;
+        }
+        Ok(())
+    }
+
+    fn _seek(&self, offset: i64, whence: i64) -> io::Result<u64> {
+        let pos = match whence {
+            SEEK_SET => SeekFrom::Start(
+                offset
+                    .try_into()
+                    .map_err(|_| invalid_input("Cannot seek to a negative position"))?,
+            ),
+            SEEK_CUR => SeekFrom::Current(offset),
+            SEEK_END => SeekFrom::End(offset),
+            _ => return Err(invalid_input("Invalid whence")),
+        };
+        let mut inner = self.lock();
+        if !inner.seekable {
+            return Err(unsupported("Stream is not seekable"));
+        }
+        inner.io()?.seek(pos)
+    }
+
+    fn _tell(&self) -> io::Result<u64> {
+        self.lock().io()?.stream_position()
+    }
+
+    fn _eof(&self) -> io::Result<bool> {
+        self.lock().resource()?.eof()
+    }
+
+    fn php_resource(&self) -> Option<PhpResource> {
+        match self.lock().resource.as_ref()? {
+            Resource::Php(resource) => Some(resource.clone()),
+            _ => None,
+        }
+    }
+
+    fn _metadata(&self) -> Option<Metadata> {
+        let mut inner = self.lock();
+        let eof = inner
+            .resource
+            .as_mut()
+            .and_then(Resource::remaining)
+            .is_some_and(|remaining| remaining == 0);
+        let (wrapper_type, stream_type) = match inner.resource.as_ref()? {
+            Resource::Memory(_) => ("PHP", "MEMORY"),
+            Resource::File(_) => ("plainfile", "STDIO"),
+            Resource::Php(_) => return None,
+        };
+        Some(Metadata {
+            eof,
+            wrapper_type,
+            stream_type,
+            mode: inner.mode.clone(),
+            seekable: inner.seekable,
+            uri: inner.uri.clone(),
+        })
+    }
+
+    // The whole contents along with the current position, which is kept.
+    fn snapshot(&self) -> io::Result<(Vec<u8>, u64)> {
+        let mut inner = self.lock();
+        if !inner.readable {
+            return Err(unsupported("Stream is not readable"));
+        }
+        let io = inner.io()?;
+        let pos = io.stream_position()?;
+        io.rewind()?;
+        let mut buf = Vec::new();
+        io.read_to_end(&mut buf)?;
+        io.seek(SeekFrom::Start(pos))?;
+        Ok((buf, pos))
+    }
+
+    // Like `__toString`, but reporting errors: everything from the start
+    // when the stream can seek, otherwise what is left. Fails rather than
+    // buffer more than `max_size` bytes, unless that is zero.
+    pub(crate) fn contents(&self, max_size: u64) -> io::Result<Vec<u8>> {
+        if self.is_seekable() {
+            self._seek(0, SEEK_SET)?;
+        }
+        if max_size == 0 {
+            return self._get_contents();
+        }
+        let limit = usize::try_from(max_size).unwrap_or(usize::MAX);
+        let contents = self._read(limit.saturating_add(1))?;
+        if contents.len() > limit {
+            return Err(io::Error::other(format!(
+                "Body exceeds the maximum size of {max_size} bytes"
+            )));
+        }
+        Ok(contents)
+    }
+
+    // The whole contents, leaving the cursor at the start so the next read
+    // sees them again. A stream that cannot seek gives what is left.
+    pub(crate) fn contents_rewound(&self) -> io::Result<Vec<u8>> {
+        let contents = self.contents(0)?;
+        if self.is_seekable() {
+            self._seek(0, SEEK_SET)?;
+        }
+        Ok(contents)
+    }
+
+    // All of a seekable stream, leaving the cursor where it was, or what is
+    // left of any other.
+    fn _to_base64(&self) -> io::Result<String> {
+        let contents = if self.is_seekable() {
+            self.snapshot()?.0
+        } else {
+            self.contents(0)?
+        };
+        Ok(base64_encode(&contents))
+    }
+
+    // An independent memory copy at the same position, so reading one
+    // does not move the other.
+    pub(crate) fn deep_clone(&self) -> io::Result<Self> {
+        let (contents, pos) = self.snapshot()?;
+        Ok(Self::from_snapshot(contents, pos))
+    }
+
+    fn from_snapshot(contents: Vec<u8>, pos: u64) -> Self {
+        let mut cursor = Cursor::new(contents);
+        cursor.set_position(pos);
+        Self::from_memory(cursor)
+    }
+
+    fn _get_contents(&self) -> io::Result<Vec<u8>> {
+        let mut inner = self.lock();
+        if !inner.readable {
+            return Err(unsupported("Stream is not readable"));
+        }
+        let mut buf = Vec::new();
+        inner.io()?.read_to_end(&mut buf)?;
+        Ok(buf)
+    }
+}
+
+fn open_options(mode: &str) -> Option<(OpenOptions, bool, bool)> {
+    let mode = mode.replace(['b', 't'], "");
+    let (base, plus) = match mode.strip_suffix('+') {
+        Some(base) => (base, true),
+        None => (mode.as_str(), false),
+    };
+    let mut options = OpenOptions::new();
+    let (readable, writable) = match base {
+        "r" => (true, plus),
+        "w" => {
+            options.create(true).truncate(true);
+            (plus, true)
+        }
+        "a" => {
+            options.create(true).append(true);
+            (plus, true)
+        }
+        "x" => {
+            options.create_new(true);
+            (plus, true)
+        }
+        "c" => {
+            options.create(true);
+            (plus, true)
+        }
+        _ => return None,
+    };
+    options.read(readable).write(writable);
+    Some((options, readable, writable))
+}
+
+fn unsupported(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::Unsupported, message)
+}
+
+fn invalid_input(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+fn runtime_error(err: io::Error) -> PhpException {
+    PhpException::new(err.to_string(), 0, stream_exception())
+}
+
+#[php_impl]
+impl Stream {
+    pub fn __construct(contents: Option<Binary<u8>>) -> Self {
+        Self::from_bytes(contents.map(Vec::from).unwrap_or_default())
+    }
+
+    pub fn from_file(path: &str, mode: Option<&str>) -> PhpResult<Self> {
+        Self::open(path, mode.unwrap_or("r"))
+            .map_err(|err| PhpException::new(err, 0, invalid_argument_exception()))
+    }
+
+    /// An in-memory stream holding the decoded `data`.
+    pub fn from_base64(data: &str) -> PhpResult<Self> {
+        base64_decode(data.as_bytes())
+            .map(Self::from_bytes)
+            .ok_or_else(|| {
+                PhpException::new(
+                    "Invalid base64 data".into(),
+                    0,
+                    invalid_argument_exception(),
+                )
+            })
+    }
+
+    /// The whole contents, base64-encoded; the cursor is left where it was.
+    pub fn to_base64(&self) -> PhpResult<String> {
+        self._to_base64().map_err(runtime_error)
+    }
+
+    pub fn read(&self, length: i64) -> PhpResult<Binary<u8>> {
+        let length = length.try_into().map_err(|_| {
+            PhpException::new(
+                "Length must be non-negative".into(),
+                0,
+                invalid_argument_exception(),
+            )
+        })?;
+        self._read(length).map(Binary::from).map_err(runtime_error)
+    }
+
+    /// Reads the next line including its ending, or at most `maxLength`
+    /// bytes of it. Returns "" at the end of the stream.
+    pub fn read_line(&self, max_length: Option<i64>) -> PhpResult<Binary<u8>> {
+        let max_length = max_length.map(usize::try_from).transpose().map_err(|_| {
+            PhpException::new(
+                "Length must be non-negative".into(),
+                0,
+                invalid_argument_exception(),
+            )
+        })?;
+        self._read_line(max_length)
+            .map(Binary::from)
+            .map_err(runtime_error)
+    }
+
+    /// Up to `length` bytes from `offset`, without moving the cursor, so
+    /// that readers sharing the stream do not disturb each other.
+    pub fn read_at(&self, offset: i64, length: i64) -> PhpResult<Binary<u8>> {
+        let (Ok(offset), Ok(length)) = (u64::try_from(offset), usize::try_from(length)) else {
+            return Err(PhpException::new(
+                "Offset and length must be non-negative".into(),
+                0,
+                invalid_argument_exception(),
+            ));
+        };
+        self._read_at(offset, length)
+            .map(Binary::from)
+            .map_err(runtime_error)
+    }
+
+    /// The bytes from `start` to `end` inclusive, or to the end of the
+    /// stream when `end` is null. The cursor is left where it was.
+    pub fn get_range(&self, start: i64, end: Option<i64>) -> PhpResult<Binary<u8>> {
+        let invalid_range = || {
+            PhpException::new(
+                "Range must be non-negative".into(),
+                0,
+                invalid_argument_exception(),
+            )
+        };
+        let start = u64::try_from(start).map_err(|_| invalid_range())?;
+        let end = end
+            .map(u64::try_from)
+            .transpose()
+            .map_err(|_| invalid_range())?;
+        self._get_range(start, end)
+            .map(Binary::from)
+            .map_err(|err| match err.kind() {
+                io::ErrorKind::InvalidInput => {
+                    PhpException::new(err.to_string(), 0, invalid_argument_exception())
+                }
+                _ => runtime_error(err),
+            })
+    }
+
+    /// Sends the rest of the stream to the output, `chunkSize` bytes at a
+    /// time with a flush after each, and returns the number of bytes sent.
+    pub fn pipe(&self, chunk_size: Option<i64>) -> PhpResult<i64> {
+        let chunk_size = match chunk_size.unwrap_or(8192) {
+            size @ 1.. => size as usize,
+            _ => {
+                return Err(PhpException::new(
+                    "Chunk size must be positive".into(),
+                    0,
+                    invalid_argument_exception(),
+                ))
+            }
+        };
+        let written = self
+            ._pipe(chunk_size, |chunk| {
+                let mut data = Zval::new();
+                data.set_binary(chunk.to_vec());
+                call("printf", vec![&"%s", &data])?;
+                call("flush", vec![]).map(drop)
+            })
+            .map_err(runtime_error)?;
+        Ok(written as i64)
+    }
+
+    pub fn write(&self, string: Binary<u8>) -> PhpResult<i64> {
+        let written = self._write(&string).map_err(runtime_error)?;
+        Ok(written as i64)
+    }
+
+    /// Cuts the stream to `size` bytes or pads it with zeros up to that.
+    pub fn truncate(&self, size: i64) -> PhpResult<()> {
+        let size = size.try_into().map_err(|_| {
+            PhpException::new(
+                "Size must be non-negative".into(),
+                0,
+                invalid_argument_exception(),
+            )
+        })?;
+        self._truncate(size).map_err(runtime_error)
+    }
+
+    pub fn seek(&self, offset: i64, whence: Option<i64>) -> PhpResult<()> {
+        self._seek(offset, whence.unwrap_or(SEEK_SET))
+            .map_err(runtime_error)?;
+        Ok(())
+    }
+
+    pub fn tell(&self) -> PhpResult<i64> {
+        let pos = self._tell().map_err(runtime_error)?;
+        Ok(pos as i64)
+    }
+
+    pub fn eof(&self) -> PhpResult<bool> {
+        self._eof().map_err(runtime_error)
+    }
+
+    pub fn get_size(&self) -> Option<i64> {
+        let inner = self.lock();
+        let size = inner.resource.as_ref()?.size().ok()?;
+        Some(size as i64)
+    }
+
+    pub fn get_contents(&self) -> PhpResult<Binary<u8>> {
+        self._get_contents()
+            .map(Binary::from)
+            .map_err(runtime_error)
+    }
+
+    pub fn rewind(&self) -> PhpResult<()> {
+        self.seek(0, None)
+    }
+
+    pub fn is_readable(&self) -> bool {
+        self.lock().readable
+    }
+
+    pub fn is_writable(&self) -> bool {
+        self.lock().writable
+    }
+
+    pub fn is_seekable(&self) -> bool {
+        self.lock().seekable
+    }
+
+    pub fn close(&self) -> PhpResult<()> {
+        let resource = self.lock().detach();
+        if let Some(Resource::Php(resource)) = resource {
+            resource.close().map_err(runtime_error)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the PHP resource the stream wraps, if any. Memory and file
+    /// streams opened by the extension have none, so they give null.
+    pub fn detach(&self) -> Option<Zval> {
+        match self.lock().detach()? {
+            Resource::Php(resource) => Some(resource.into_zval()),
+            _ => None,
+        }
+    }
+
+    /// Returns the metadata `stream_get_meta_data()` would, or the value
+    /// for `key`; a detached stream has none.
+    pub fn get_metadata(&self, key: Option<&str>) -> PhpResult<Zval> {
+        if let Some(resource) = self.php_resource() {
+            let meta_data = resource.meta_data().map_err(runtime_error)?;
+            return Ok(match key {
+                Some(key) => meta_data
+                    .array()
+                    .and_then(|table| table.get(key))
+                    .map(Zval::shallow_clone)
+                    .unwrap_or_else(Zval::new),
+                None => meta_data,
+            });
+        }
+        let mut table = ZendHashTable::new();
+        if let Some(metadata) = self._metadata() {
+            table.insert("timed_out", false)?;
+            table.insert("blocked", true)?;
+            table.insert("eof", metadata.eof)?;
+            table.insert("wrapper_type", metadata.wrapper_type)?;
+            table.insert("stream_type", metadata.stream_type)?;
+            table.insert("mode", metadata.mode)?;
+            table.insert("unread_bytes", 0)?;
+            table.insert("seekable", metadata.seekable)?;
+            table.insert("uri", metadata.uri)?;
+        }
+        let mut value = Zval::new();
+        match key {
+            Some(key) => {
+                if let Some(entry) = table.get(key) {
+                    value = entry.shallow_clone();
+                }
+            }
+            None => value.set_hashtable(table),
+        }
+        Ok(value)
+    }
+
+    // PSR-7 forbids `__toString` from throwing, so failures yield "". A
+    // stream that cannot seek gives what is left from its position.
+    #[rename("__toString")]
+    pub fn to_string(&self) -> Binary<u8> {
+        if self.is_seekable() && self._seek(0, SEEK_SET).is_err() {
+            return Binary::from(Vec::new());
+        }
+        self._get_contents().unwrap_or_default().into()
+    }
+
+    #[rename("__debugInfo")]
+    pub fn debug_info(&self) -> PhpResult<ZBox<ZendHashTable>> {
+        let mut info = ZendHashTable::new();
+        info.insert("size", self.get_size())?;
+        info.insert("position", self._tell().ok().map(|pos| pos as i64))?;
+        info.insert("readable", self.is_readable())?;
+        info.insert("writable", self.is_writable())?;
+        Ok(info)
+    }
+
+    /// File and resource-backed streams are unserialized as memory streams
+    /// holding their contents.
+    #[rename("__serialize")]
+    pub fn serialize(&self) -> PhpResult<ZBox<ZendHashTable>> {
+        let (contents, pos) = self.snapshot().map_err(runtime_error)?;
+        let mut data = ZendHashTable::new();
+        data.insert("contents", Binary::from(contents))?;
+        data.insert("position", pos as i64)?;
+        Ok(data)
+    }
+
+    #[rename("__unserialize")]
+    pub fn unserialize(
+        #[this] this: &mut ZendClassObject<Self>,
+        data: &ZendHashTable,
+    ) -> PhpResult<()> {
+        let contents = data
+            .get("contents")
+            .and_then(Zval::binary::<u8>)
+            .ok_or_else(|| invalid_serialized_data("stream"))?;
+        let pos = data
+            .get("position")
+            .and_then(Zval::long)
+            .and_then(|pos| pos.try_into().ok())
+            .unwrap_or(0);
+        this.initialize(Self::from_snapshot(contents, pos));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("psr7-rs-{}-{name}", std::process::id()));
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn memory_read() {
+        let stream = Stream::from_bytes(b"hello world".to_vec());
+        assert_eq!(stream._read(5).unwrap(), b"hello");
+        assert_eq!(stream._tell().unwrap(), 5);
+        assert_eq!(stream._get_contents().unwrap(), b" world");
+        assert!(stream._eof().unwrap());
+    }
+
+    #[test]
+    fn snapshot_keeps_position() {
+        let stream = Stream::from_bytes(b"hello world".to_vec());
+        stream._seek(6, SEEK_SET).unwrap();
+        let (contents, pos) = stream.snapshot().unwrap();
+        assert_eq!(contents, b"hello world");
+        assert_eq!(pos, 6);
+        assert_eq!(stream._tell().unwrap(), 6);
+
+        let restored = Stream::from_snapshot(contents, pos);
+        assert_eq!(restored._get_contents().unwrap(), b"world");
+    }
+
+    #[test]
+    fn memory_read_past_end() {
+        let stream = Stream::from_bytes(b"abc".to_vec());
+        assert_eq!(stream._read(10).unwrap(), b"abc");
+        assert_eq!(stream._read(10).unwrap(), b"");
+    }
+
+    #[test]
+    fn memory_write() {
+        let stream = Stream::from_bytes(Vec::new());
+        assert_eq!(stream._write(b"foo").unwrap(), 3);
+        assert_eq!(stream._write(b"bar").unwrap(), 3);
+        assert_eq!(stream.get_size(), Some(6));
+        assert_eq!(*stream.to_string(), b"foobar");
+    }
+
+    #[test]
+    fn memory_seek() {
+        let stream = Stream::from_bytes(b"0123456789".to_vec());
+        assert_eq!(stream._seek(3, SEEK_SET).unwrap(), 3);
+        assert_eq!(stream._seek(2, SEEK_CUR).unwrap(), 5);
+        assert_eq!(stream._seek(-1, SEEK_END).unwrap(), 9);
+        assert_eq!(stream._read(1).unwrap(), b"9");
+        assert!(stream._seek(-1, SEEK_SET).is_err());
+        assert!(stream._seek(0, 3).is_err());
+    }
+
+    #[test]
+    fn read_line() {
+        let stream = Stream::from_bytes(b"first\nsecond\r\n\nlast".to_vec());
+        assert_eq!(stream._read_line(None).unwrap(), b"first\n");
+        assert_eq!(stream._read_line(None).unwrap(), b"second\r\n");
+        assert_eq!(stream._read_line(None).unwrap(), b"\n");
+        assert_eq!(stream._read_line(None).unwrap(), b"last");
+        assert_eq!(stream._read_line(None).unwrap(), b"");
+        assert!(stream._eof().unwrap());
+    }
+
+    #[test]
+    fn read_line_max_length() {
+        let stream = Stream::from_bytes(b"abcdef\r\nx".to_vec());
+        assert_eq!(stream._read_line(Some(4)).unwrap(), b"abcd");
+        assert_eq!(stream._read_line(Some(0)).unwrap(), b"");
+        assert_eq!(stream._read_line(Some(10)).unwrap(), b"ef\r\n");
+        assert_eq!(stream._tell().unwrap(), 8);
+    }
+
+    #[test]
+    fn contents_max_size() {
+        let stream = Stream::from_bytes(b"0123456789".to_vec());
+        stream._read(4).unwrap();
+        assert_eq!(stream.contents(10).unwrap(), b"0123456789");
+        assert_eq!(stream.contents(0).unwrap(), b"0123456789");
+        assert_eq!(
+            stream.contents(9).unwrap_err().to_string(),
+            "Body exceeds the maximum size of 9 bytes"
+        );
+    }
+
+    #[test]
+    fn truncate_smaller() {
+        let stream = Stream::from_bytes(b"0123456789".to_vec());
+        stream._seek(8, SEEK_SET).unwrap();
+        stream._truncate(4).unwrap();
+        assert_eq!(stream._tell().unwrap(), 4);
+        assert_eq!(stream.get_size(), Some(4));
+        assert_eq!(*stream.to_string(), b"0123");
+    }
+
+    #[test]
+    fn truncate_larger() {
+        let stream = Stream::from_bytes(b"ab".to_vec());
+        stream._seek(1, SEEK_SET).unwrap();
+        stream._truncate(5).unwrap();
+        assert_eq!(stream._tell().unwrap(), 1);
+        assert_eq!(*stream.to_string(), b"ab\0\0\0");
+    }
+
+    #[test]
+    fn truncate_file() {
+        let path = temp_path("truncate");
+        std::fs::write(&path, "0123456789").unwrap();
+        let stream = Stream::open(&path, "r+").unwrap();
+        stream._seek(0, SEEK_END).unwrap();
+        stream._truncate(3).unwrap();
+        assert_eq!(stream._tell().unwrap(), 3);
+        stream._truncate(4).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"012\0");
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn truncate_read_only() {
+        let stream = Stream::from_bytes(b"data".to_vec());
+        stream.lock().writable = false;
+        assert!(stream._truncate(2).is_err());
+        assert_eq!(*stream.to_string(), b"data");
+    }
+
+    #[test]
+    fn pipe_in_chunks() {
+        let stream = Stream::from_bytes(b"0123456789abcd".to_vec());
+        stream._seek(2, SEEK_SET).unwrap();
+        let mut chunks = Vec::new();
+        let written = stream
+            ._pipe(5, |chunk| {
+                chunks.push(chunk.to_vec());
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(written, 12);
+        assert_eq!(chunks, [&b"23456"[..], b"789ab", b"cd"]);
+        assert!(stream._eof().unwrap());
+    }
+
+    #[test]
+    fn contents_rewound() {
+        let stream = Stream::from_bytes(b"body".to_vec());
+        stream._read(2).unwrap();
+        assert_eq!(stream.contents_rewound().unwrap(), b"body");
+        assert_eq!(stream._tell().unwrap(), 0);
+        assert_eq!(stream.contents_rewound().unwrap(), b"body");
+        assert_eq!(stream._get_contents().unwrap(), b"body");
+    }
+
+    #[test]
+    fn contents_rewound_not_seekable() {
+        let stream = Stream::from_bytes(b"pipe data".to_vec());
+        stream._read(5).unwrap();
+        stream.lock().seekable = false;
+        assert_eq!(stream.contents_rewound().unwrap(), b"data");
+        assert_eq!(stream.contents_rewound().unwrap(), b"");
+    }
+
+    #[test]
+    fn base64_round_trip() {
+        let stream = Stream::from_bytes(b"\x00binary\xff body".to_vec());
+        stream._seek(3, SEEK_SET).unwrap();
+        let encoded = stream._to_base64().unwrap();
+        assert_eq!(encoded, "AGJpbmFyef8gYm9keQ==");
+        assert_eq!(stream._tell().unwrap(), 3);
+
+        let decoded = Stream::from_base64(&encoded).unwrap();
+        assert_eq!(decoded._tell().unwrap(), 0);
+        assert_eq!(*decoded.to_string(), b"\x00binary\xff body");
+        assert_eq!(Stream::from_base64("").unwrap().get_size(), Some(0));
+    }
+
+    #[test]
+    fn base64_not_seekable() {
+        let stream = Stream::from_bytes(b"pipe data".to_vec());
+        stream._read(5).unwrap();
+        stream.lock().seekable = false;
+        assert_eq!(stream._to_base64().unwrap(), "ZGF0YQ==");
+    }
+
+    #[test]
+    fn read_at() {
+        let stream = Stream::from_bytes(b"0123456789".to_vec());
+        stream._seek(7, SEEK_SET).unwrap();
+        assert_eq!(stream._read_at(2, 3).unwrap(), b"234");
+        assert_eq!(stream._read_at(8, 5).unwrap(), b"89");
+        assert_eq!(stream._read_at(20, 5).unwrap(), b"");
+        assert_eq!(stream._tell().unwrap(), 7);
+        assert_eq!(stream._read(2).unwrap(), b"78");
+
+        stream.lock().seekable = false;
+        assert!(stream._read_at(0, 1).is_err());
+    }
+
+    #[test]
+    fn get_range() {
+        let stream = Stream::from_bytes(b"0123456789".to_vec());
+        stream._seek(7, SEEK_SET).unwrap();
+        assert_eq!(stream._get_range(2, Some(5)).unwrap(), b"2345");
+        assert_eq!(stream._get_range(4, Some(4)).unwrap(), b"4");
+        assert_eq!(stream._get_range(6, None).unwrap(), b"6789");
+        assert_eq!(stream._tell().unwrap(), 7);
+    }
+
+    #[test]
+    fn get_range_invalid() {
+        let stream = Stream::from_bytes(b"0123456789".to_vec());
+        stream._seek(3, SEEK_SET).unwrap();
+        for (start, end) in [(5, Some(2)), (0, Some(10)), (10, None)] {
+            let err = stream._get_range(start, end).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput, "{start}..={end:?}");
+        }
+        assert!(Stream::from_bytes(Vec::new())._get_range(0, None).is_err());
+        assert_eq!(stream._tell().unwrap(), 3);
+    }
+
+    #[test]
+    fn to_string_reads_from_start() {
+        let stream = Stream::from_bytes(b"body".to_vec());
+        stream._read(2).unwrap();
+        assert_eq!(*stream.to_string(), b"body");
+    }
+
+    #[test]
+    fn to_string_not_seekable() {
+        let stream = Stream::from_bytes(b"pipe data".to_vec());
+        stream._read(5).unwrap();
+        stream.lock().seekable = false;
+        assert_eq!(*stream.to_string(), b"data");
+        assert_eq!(*stream.to_string(), b"");
+    }
+
+    #[test]
+    fn clones_share_position() {
+        let stream = Stream::from_bytes(b"abcdef".to_vec());
+        let clone = stream.clone();
+        stream._read(2).unwrap();
+        assert_eq!(clone._tell().unwrap(), 2);
+    }
+
+    #[test]
+    fn file_write_then_read() {
+        let path = temp_path("write-read");
+        let stream = Stream::open(&path, "w+").unwrap();
+        assert!(stream.is_readable() && stream.is_writable());
+        stream._write(b"file body").unwrap();
+        stream._seek(0, SEEK_SET).unwrap();
+        assert_eq!(stream._get_contents().unwrap(), b"file body");
+        assert_eq!(stream.get_size(), Some(9));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn file_read_only() {
+        let path = temp_path("read-only");
+        std::fs::write(&path, "content").unwrap();
+        let stream = Stream::open(&path, "rb").unwrap();
+        assert!(stream.is_readable());
+        assert!(!stream.is_writable());
+        assert!(stream._write(b"x").is_err());
+        assert_eq!(stream._read(4).unwrap(), b"cont");
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn file_write_only() {
+        let path = temp_path("write-only");
+        let stream = Stream::open(&path, "a").unwrap();
+        assert!(!stream.is_readable());
+        assert!(stream._read(1).is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn read_huge_length() {
+        let stream = Stream::from_bytes(b"abc".to_vec());
+        assert_eq!(stream._read(usize::MAX).unwrap(), b"abc");
+    }
+
+    #[test]
+    fn eof_error_when_detached() {
+        let stream = Stream::from_bytes(b"abc".to_vec());
+        stream.lock().detach();
+        assert!(stream._eof().is_err());
+        assert!(stream._read(1).is_err());
+        assert_eq!(stream.get_size(), None);
+        assert!(!stream.is_readable() && !stream.is_seekable());
+    }
+
+    #[test]
+    fn metadata() {
+        let stream = Stream::from_bytes(b"abc".to_vec());
+        let metadata = stream._metadata().unwrap();
+        assert_eq!(metadata.uri, "php://memory");
+        assert!(metadata.seekable && !metadata.eof);
+        stream._read(3).unwrap();
+        assert!(stream._metadata().unwrap().eof);
+
+        let path = temp_path("metadata");
+        let stream = Stream::open(&path, "w+").unwrap();
+        let metadata = stream._metadata().unwrap();
+        assert_eq!(metadata.wrapper_type, "plainfile");
+        assert_eq!(metadata.mode, "w+");
+        assert_eq!(metadata.uri, path);
+        stream.lock().detach();
+        assert_eq!(stream._metadata(), None);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn character_device_not_seekable() {
+        let stream = Stream::open("/dev/null", "r").unwrap();
+        assert!(!stream.is_seekable());
+        assert!(stream._seek(0, SEEK_SET).is_err());
+    }
+
+    #[test]
+    fn open_invalid_mode() {
+        assert!(Stream::open(&temp_path("invalid-mode"), "q").is_err());
+    }
+
+    #[test]
+    fn open_missing_file() {
+        assert!(Stream::open(&temp_path("missing"), "r").is_err());
+    }
+}
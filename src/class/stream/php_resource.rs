@@ -0,0 +1,140 @@
+use super::{SEEK_CUR, SEEK_END, SEEK_SET};
+use ext_php_rs::convert::IntoZvalDyn;
+use ext_php_rs::types::{ZendCallable, Zval};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+// A stream resource owned by PHP (`fopen()`, `php://temp`, sockets, ...).
+// It is driven through PHP's stream functions, so wrappers, filters and
+// `open_basedir` behave as they do in userland.
+pub(super) struct PhpResource {
+    resource: Zval,
+}
+
+impl PhpResource {
+    pub(super) fn new(resource: &Zval) -> Self {
+        Self {
+            resource: resource.shallow_clone(),
+        }
+    }
+
+    pub(super) fn into_zval(self) -> Zval {
+        self.resource
+    }
+
+    /// The array returned by `stream_get_meta_data()`.
+    pub(super) fn meta_data(&self) -> io::Result<Zval> {
+        call("stream_get_meta_data", vec![&self.resource])
+    }
+
+    pub(super) fn size(&self) -> io::Result<u64> {
+        call("fstat", vec![&self.resource])?
+            .array()
+            .and_then(|stat| stat.get("size"))
+            .and_then(Zval::long)
+            .and_then(|size| u64::try_from(size).ok())
+            .ok_or_else(|| io::Error::other("Failed to get the stream size"))
+    }
+
+    pub(super) fn truncate(&self, size: u64) -> io::Result<()> {
+        let size = i64::try_from(size).map_err(io::Error::other)?;
+        if call("ftruncate", vec![&self.resource, &size])?.bool() != Some(true) {
+            return Err(io::Error::other("Failed to truncate the stream"));
+        }
+        Ok(())
+    }
+
+    pub(super) fn eof(&self) -> io::Result<bool> {
+        Ok(call("feof", vec![&self.resource])?.bool() != Some(false))
+    }
+
+    pub(super) fn close(self) -> io::Result<()> {
+        call("fclose", vec![&self.resource]).map(drop)
+    }
+}
+
+impl Clone for PhpResource {
+    fn clone(&self) -> Self {
+        Self::new(&self.resource)
+    }
+}
+
+impl Read for PhpResource {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let data = call("fread", vec![&self.resource, &(buf.len() as i64)])?
+            .binary::<u8>()
+            .ok_or_else(|| io::Error::other("Failed to read from the stream"))?;
+        let len = data.len().min(buf.len());
+        buf[..len].copy_from_slice(&data[..len]);
+        Ok(len)
+    }
+}
+
+impl Write for PhpResource {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut data = Zval::new();
+        data.set_binary(buf.to_vec());
+        call("fwrite", vec![&self.resource, &data])?
+            .long()
+            .and_then(|written| usize::try_from(written).ok())
+            .ok_or_else(|| io::Error::other("Failed to write to the stream"))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        call("fflush", vec![&self.resource]).map(drop)
+    }
+}
+
+impl Seek for PhpResource {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let (offset, whence) = match pos {
+            SeekFrom::Start(offset) => (i64::try_from(offset).map_err(io::Error::other)?, SEEK_SET),
+            SeekFrom::Current(offset) => (offset, SEEK_CUR),
+            SeekFrom::End(offset) => (offset, SEEK_END),
+        };
+        if call("fseek", vec![&self.resource, &offset, &whence])?.long() != Some(0) {
+            return Err(io::Error::other("Failed to seek in the stream"));
+        }
+        self.stream_position()
+    }
+
+    fn stream_position(&mut self) -> io::Result<u64> {
+        call("ftell", vec![&self.resource])?
+            .long()
+            .and_then(|pos| u64::try_from(pos).ok())
+            .ok_or_else(|| io::Error::other("Failed to get the stream position"))
+    }
+}
+
+/// Whether a stream opened in `mode` (as reported by
+/// `stream_get_meta_data()`) can be read and written.
+pub(super) fn access(mode: &str) -> (bool, bool) {
+    let readable = mode.contains(['r', '+']);
+    let writable = mode.contains(['w', 'a', 'x', 'c', '+']);
+    (readable, writable)
+}
+
+pub(super) fn call(name: &str, args: Vec<&dyn IntoZvalDyn>) -> io::Result<Zval> {
+    ZendCallable::try_from_name(name)
+        .and_then(|function| function.try_call(args))
+        .map_err(|err| io::Error::other(format!("{name}() failed: {err}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn access_modes() {
+        assert_eq!(access("r"), (true, false));
+        assert_eq!(access("rb"), (true, false));
+        assert_eq!(access("r+b"), (true, true));
+        assert_eq!(access("w+b"), (true, true));
+        assert_eq!(access("wb"), (false, true));
+        assert_eq!(access("a"), (false, true));
+        assert_eq!(access("x+"), (true, true));
+        assert_eq!(access("c"), (false, true));
+    }
+}
@@ -0,0 +1,10 @@
+use crate::util::runtime_exception;
+use ext_php_rs::prelude::*;
+
+// Thrown when a stream operation fails, so callers can tell I/O failures
+// apart from other runtime errors while `catch (RuntimeException)` keeps
+// working.
+#[php_class(name = "Takaram\\Psr7\\Exception\\StreamException")]
+#[extends(runtime_exception())]
+#[derive(Default)]
+pub struct StreamException;
@@ -0,0 +1,45 @@
+use crate::class::stream::Stream;
+use crate::util::invalid_argument_exception;
+use ext_php_rs::binary::Binary;
+use ext_php_rs::prelude::*;
+use ext_php_rs::types::Zval;
+
+// PSR-17 StreamFactoryInterface.
+#[php_class(name = "Takaram\\Psr7\\Internal\\StreamFactory")]
+pub struct StreamFactory;
+
+#[php_impl]
+impl StreamFactory {
+    pub fn __construct() -> Self {
+        Self
+    }
+
+    pub fn create_stream(&self, content: Option<Binary<u8>>) -> Stream {
+        Stream::__construct(content)
+    }
+
+    /// Opens the file with `fopen()`, so stream wrappers work and
+    /// `open_basedir` applies.
+    pub fn create_stream_from_file(&self, filename: &str, mode: Option<&str>) -> PhpResult<Stream> {
+        Stream::fopen(filename, mode.unwrap_or("r"))
+    }
+
+    /// The stream reads from and writes to the resource itself.
+    pub fn create_stream_from_resource(&self, resource: &Zval) -> PhpResult<Stream> {
+        Stream::from_php_resource(resource)
+    }
+}
+
+// Message bodies may be given as a string or as a Stream.
+pub(crate) fn stream_argument(body: &Zval) -> PhpResult<Stream> {
+    if let Some(stream) = body.extract::<&Stream>() {
+        return Ok(stream.clone());
+    }
+    body.binary::<u8>().map(Stream::from_bytes).ok_or_else(|| {
+        PhpException::new(
+            "Body must be a string or a Stream".into(),
+            0,
+            invalid_argument_exception(),
+        )
+    })
+}
@@ -0,0 +1,538 @@
+use crate::class::stream::Stream;
+use crate::util::{invalid_argument_exception, runtime_exception};
+use ext_php_rs::boxed::ZBox;
+use ext_php_rs::prelude::*;
+use ext_php_rs::types::{ZendCallable, ZendHashTable};
+use ext_php_rs::zend::ExecutorGlobals;
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+pub(crate) const UPLOAD_ERR_OK: i64 = 0;
+pub(crate) const UPLOAD_ERR_NO_FILE: i64 = 4;
+const UPLOAD_ERR_CODES: [i64; 8] = [0, 1, 2, 3, 4, 6, 7, 8];
+
+static TEMP_FILES: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Clone)]
+enum Source {
+    Path(String),
+    Stream(Stream),
+}
+
+#[php_class(name = "Takaram\\Psr7\\Internal\\UploadedFile")]
+#[derive(Clone)]
+pub struct UploadedFile {
+    source: Source,
+    size: Option<i64>,
+    error: i64,
+    client_filename: Option<String>,
+    client_media_type: Option<String>,
+    moved: bool,
+}
+
+impl UploadedFile {
+    fn new(
+        source: Source,
+        size: Option<i64>,
+        error: i64,
+        client_filename: Option<String>,
+        client_media_type: Option<String>,
+    ) -> Result<Self, &'static str> {
+        if !UPLOAD_ERR_CODES.contains(&error) {
+            return Err("Invalid upload error code");
+        }
+        Ok(Self {
+            source,
+            size,
+            error,
+            client_filename,
+            client_media_type,
+            moved: false,
+        })
+    }
+
+    // A file received in a request body rather than through the SAPI.
+    pub(crate) fn from_contents(
+        contents: Vec<u8>,
+        error: i64,
+        client_filename: Option<String>,
+        client_media_type: Option<String>,
+    ) -> Self {
+        Self {
+            size: Some(contents.len() as i64),
+            source: Source::Stream(Stream::from_bytes(contents)),
+            error,
+            client_filename,
+            client_media_type,
+            moved: false,
+        }
+    }
+
+    fn check_available(&self) -> Result<(), String> {
+        if self.error != UPLOAD_ERR_OK {
+            return Err("Cannot retrieve the file due to an upload error".into());
+        }
+        if self.moved {
+            return Err("The uploaded file has already been moved".into());
+        }
+        Ok(())
+    }
+
+    // The size of the underlying stream, which may disagree with the size
+    // the file was declared with.
+    fn stream_size(&self) -> Option<i64> {
+        match &self.source {
+            Source::Path(_) => None,
+            Source::Stream(stream) => stream.get_size(),
+        }
+    }
+
+    fn _validate(&self) -> Result<(), String> {
+        match (self.size, self.stream_size()) {
+            (Some(declared), Some(actual)) if declared != actual => Err(format!(
+                "Declared size of {declared} bytes does not match the stream size of {actual} bytes"
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    fn _get_stream(&self) -> Result<Stream, String> {
+        self.check_available()?;
+        match &self.source {
+            Source::Path(path) => Stream::open(path, "r"),
+            Source::Stream(stream) => Ok(stream.clone()),
+        }
+    }
+
+    // Relative paths are taken from `cwd`, which is PHP's working directory
+    // rather than the process's under ZTS.
+    fn _move_to(&mut self, target_path: &str, cwd: &Path) -> Result<(), String> {
+        self.check_available()?;
+        if target_path.is_empty() {
+            return Err("Target path must be a non-empty string".into());
+        }
+        let target = cwd.join(target_path);
+        let target = target.as_path();
+        let moved = match &self.source {
+            // `rename` fails across filesystems, so fall back to copying.
+            Source::Path(path) => {
+                let path = cwd.join(path);
+                fs::rename(&path, target).or_else(|_| {
+                    write_atomically(target, |file| io::copy(&mut File::open(&path)?, file))
+                        .and_then(|_| fs::remove_file(&path))
+                })
+            }
+            Source::Stream(stream) => write_atomically(target, |file| stream.copy_to(file)),
+        };
+        moved.map_err(|err| format!("Failed to move uploaded file to {target_path}: {err}"))?;
+        self.moved = true;
+        Ok(())
+    }
+
+    // Files PHP received in this request must be moved with
+    // `move_uploaded_file()`, which also checks `open_basedir`.
+    fn move_sapi_upload(&mut self, target_path: &str) -> PhpResult<bool> {
+        let Source::Path(path) = &self.source else {
+            return Ok(false);
+        };
+        let uploaded = ZendCallable::try_from_name("is_uploaded_file")?.try_call(vec![path])?;
+        if uploaded.bool() != Some(true) {
+            return Ok(false);
+        }
+        let moved = ZendCallable::try_from_name("move_uploaded_file")?
+            .try_call(vec![path, &target_path])?;
+        if moved.bool() != Some(true) {
+            return Err(PhpException::new(
+                format!("Failed to move uploaded file to {target_path}"),
+                0,
+                runtime_exception(),
+            ));
+        }
+        self.moved = true;
+        Ok(true)
+    }
+}
+
+// Writes to a temporary file next to `target` and renames it into place,
+// so a failed copy never leaves a partial target behind.
+fn write_atomically(
+    target: &Path,
+    write: impl FnOnce(&mut File) -> io::Result<u64>,
+) -> io::Result<()> {
+    let name = target
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "No file name in path"))?;
+    let mut temp_name = OsString::from(".");
+    temp_name.push(name);
+    temp_name.push(format!(
+        ".{}.{}.tmp",
+        process::id(),
+        TEMP_FILES.fetch_add(1, Ordering::Relaxed)
+    ));
+    let temp = target.with_file_name(temp_name);
+    let result = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&temp)
+        .and_then(|mut file| {
+            write(&mut file)?;
+            file.sync_all()
+        })
+        .and_then(|_| fs::rename(&temp, target));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+// PHP's `open_basedir` check: the absolute `path` must resolve to a
+// location inside one of the listed directories. Symlinks are followed, as
+// `File::open` and `rename` would. An empty setting allows everything.
+fn is_allowed_by_open_basedir(path: &Path, open_basedir: &str) -> bool {
+    if open_basedir.is_empty() {
+        return true;
+    }
+    let Some(resolved) = resolve(path) else {
+        return false;
+    };
+    std::env::split_paths(open_basedir)
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .any(|dir| fs::canonicalize(dir).is_ok_and(|dir| resolved.starts_with(dir)))
+}
+
+// The canonical form of `path`. A path that does not exist yet, such as a
+// move target, is resolved through its parent directory.
+fn resolve(path: &Path) -> Option<PathBuf> {
+    if let Ok(resolved) = fs::canonicalize(path) {
+        return Some(resolved);
+    }
+    Some(
+        fs::canonicalize(path.parent()?)
+            .ok()?
+            .join(path.file_name()?),
+    )
+}
+
+fn working_directory() -> PhpResult<PathBuf> {
+    let cwd = ZendCallable::try_from_name("getcwd")?.try_call(vec![])?;
+    match cwd.string() {
+        Some(cwd) => Ok(PathBuf::from(cwd)),
+        None => std::env::current_dir().map_err(|err| {
+            PhpException::new(
+                format!("Failed to get the working directory: {err}"),
+                0,
+                runtime_exception(),
+            )
+        }),
+    }
+}
+
+fn open_basedir() -> String {
+    ExecutorGlobals::get()
+        .ini_values()
+        .get("open_basedir")
+        .cloned()
+        .flatten()
+        .unwrap_or_default()
+}
+
+#[php_impl]
+impl UploadedFile {
+    pub fn __construct(
+        file: String,
+        size: Option<i64>,
+        error: i64,
+        client_filename: Option<String>,
+        client_media_type: Option<String>,
+    ) -> PhpResult<Self> {
+        Self::new(
+            Source::Path(file),
+            size,
+            error,
+            client_filename,
+            client_media_type,
+        )
+        .map_err(|err| PhpException::new(err.into(), 0, invalid_argument_exception()))
+    }
+
+    pub fn from_stream(
+        stream: &Stream,
+        size: Option<i64>,
+        error: i64,
+        client_filename: Option<String>,
+        client_media_type: Option<String>,
+    ) -> PhpResult<Self> {
+        Self::new(
+            Source::Stream(stream.clone()),
+            size,
+            error,
+            client_filename,
+            client_media_type,
+        )
+        .map_err(|err| PhpException::new(err.into(), 0, invalid_argument_exception()))
+    }
+
+    pub fn get_stream(&self) -> PhpResult<Stream> {
+        self._get_stream()
+            .map_err(|err| PhpException::new(err, 0, runtime_exception()))
+    }
+
+    pub fn move_to(&mut self, target_path: &str) -> PhpResult<()> {
+        let to_exception = |err: String| PhpException::new(err, 0, runtime_exception());
+        self.check_available().map_err(to_exception)?;
+        if self.move_sapi_upload(target_path)? {
+            return Ok(());
+        }
+        let cwd = working_directory()?;
+        let open_basedir = open_basedir();
+        let mut paths = vec![target_path];
+        if let Source::Path(path) = &self.source {
+            paths.push(path);
+        }
+        if let Some(path) = paths
+            .into_iter()
+            .find(|path| !is_allowed_by_open_basedir(&cwd.join(path), &open_basedir))
+        {
+            return Err(to_exception(format!(
+                "open_basedir restriction in effect: {path} is not within the allowed paths"
+            )));
+        }
+        self._move_to(target_path, &cwd).map_err(to_exception)
+    }
+
+    pub fn get_size(&self) -> Option<i64> {
+        self.size
+    }
+
+    /// The size reported by the stream the file was created from, or null
+    /// when it is unknown. `getSize()` keeps returning the declared size.
+    pub fn get_stream_size(&self) -> Option<i64> {
+        self.stream_size()
+    }
+
+    /// Throws when the declared size and the stream's size are both known
+    /// and disagree.
+    pub fn validate(&self) -> PhpResult<()> {
+        self._validate()
+            .map_err(|err| PhpException::new(err, 0, invalid_argument_exception()))
+    }
+
+    pub fn get_error(&self) -> i64 {
+        self.error
+    }
+
+    pub fn get_client_filename(&self) -> Option<String> {
+        self.client_filename.clone()
+    }
+
+    pub fn get_client_media_type(&self) -> Option<String> {
+        self.client_media_type.clone()
+    }
+
+    #[rename("__debugInfo")]
+    pub fn debug_info(&self) -> PhpResult<ZBox<ZendHashTable>> {
+        let mut info = ZendHashTable::new();
+        info.insert("clientFilename", self.get_client_filename())?;
+        info.insert("clientMediaType", self.get_client_media_type())?;
+        info.insert("size", self.get_size())?;
+        info.insert("error", self.get_error())?;
+        info.insert("moved", self.moved)?;
+        Ok(info)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("psr7-rs-{}-{name}", std::process::id()));
+        path.to_string_lossy().into_owned()
+    }
+
+    fn cwd() -> PathBuf {
+        std::env::current_dir().unwrap()
+    }
+
+    fn uploaded_path(name: &str, contents: &str) -> UploadedFile {
+        let path = temp_path(name);
+        fs::write(&path, contents).unwrap();
+        UploadedFile::new(
+            Source::Path(path),
+            Some(contents.len() as i64),
+            0,
+            None,
+            None,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn move_path() {
+        let mut file = uploaded_path("upload-src", "uploaded");
+        let target = temp_path("upload-dst");
+        file._move_to(&target, &cwd()).unwrap();
+        assert_eq!(fs::read_to_string(&target).unwrap(), "uploaded");
+        assert!(!fs::exists(temp_path("upload-src")).unwrap());
+        fs::remove_file(target).unwrap();
+    }
+
+    #[test]
+    fn move_only_once() {
+        let mut file = uploaded_path("upload-once", "data");
+        let target = temp_path("upload-once-dst");
+        file._move_to(&target, &cwd()).unwrap();
+        assert!(file
+            ._move_to(&temp_path("upload-once-again"), &cwd())
+            .is_err());
+        assert!(file._get_stream().is_err());
+        fs::remove_file(target).unwrap();
+    }
+
+    #[test]
+    fn move_stream() {
+        let stream = Stream::from_bytes(b"streamed".to_vec());
+        let mut file = UploadedFile::new(Source::Stream(stream), None, 0, None, None).unwrap();
+        let target = temp_path("upload-stream-dst");
+        file._move_to(&target, &cwd()).unwrap();
+        assert_eq!(fs::read_to_string(&target).unwrap(), "streamed");
+        fs::remove_file(target).unwrap();
+    }
+
+    #[test]
+    fn failed_copy_leaves_no_target() {
+        let target = temp_path("upload-partial");
+        let result = write_atomically(Path::new(&target), |file| {
+            io::Write::write_all(file, b"part")?;
+            Err(io::Error::other("interrupted"))
+        });
+        assert!(result.is_err());
+        assert!(!fs::exists(&target).unwrap());
+        let dir = std::env::temp_dir();
+        let prefix = format!(".psr7-rs-{}-upload-partial", process::id());
+        assert!(!fs::read_dir(dir).unwrap().any(|entry| entry
+            .unwrap()
+            .file_name()
+            .to_string_lossy()
+            .starts_with(&prefix)));
+    }
+
+    #[test]
+    fn open_basedir_check() {
+        let dir = std::env::temp_dir();
+        let inside = dir.join("psr7-rs-basedir");
+        let base = dir.to_string_lossy();
+        assert!(is_allowed_by_open_basedir(&inside, ""));
+        assert!(is_allowed_by_open_basedir(&inside, &base));
+        assert!(!is_allowed_by_open_basedir(&inside, "/nonexistent-psr7-rs"));
+        assert!(!is_allowed_by_open_basedir(
+            &dir.join("missing-psr7-rs/file"),
+            &base
+        ));
+        let list = std::env::join_paths(["/nonexistent-psr7-rs", &*base]).unwrap();
+        assert!(is_allowed_by_open_basedir(&inside, &list.to_string_lossy()));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn open_basedir_follows_symlinked_source() {
+        let allowed = PathBuf::from(temp_path("basedir-allowed"));
+        let secret = PathBuf::from(temp_path("basedir-secret"));
+        fs::create_dir_all(&allowed).unwrap();
+        fs::write(&secret, "secret").unwrap();
+        let link = allowed.join("link");
+        let _ = fs::remove_file(&link);
+        std::os::unix::fs::symlink(&secret, &link).unwrap();
+
+        let base = allowed.to_string_lossy();
+        assert!(!is_allowed_by_open_basedir(&link, &base));
+        assert!(is_allowed_by_open_basedir(&allowed.join("target"), &base));
+
+        fs::remove_file(link).unwrap();
+        fs::remove_dir(allowed).unwrap();
+        fs::remove_file(secret).unwrap();
+    }
+
+    #[test]
+    fn move_relative_to_working_directory() {
+        let mut file = uploaded_path("upload-relative", "relative");
+        let dir = std::env::temp_dir();
+        let target = format!("psr7-rs-{}-upload-relative-dst", process::id());
+        file._move_to(&target, &dir).unwrap();
+        assert_eq!(fs::read_to_string(dir.join(&target)).unwrap(), "relative");
+        fs::remove_file(dir.join(target)).unwrap();
+    }
+
+    #[test]
+    fn get_stream_from_path() {
+        let file = uploaded_path("upload-stream", "contents");
+        let stream = file._get_stream().unwrap();
+        assert_eq!(*stream.to_string(), b"contents");
+        fs::remove_file(temp_path("upload-stream")).unwrap();
+    }
+
+    #[test]
+    fn upload_error() {
+        let stream = Stream::from_bytes(Vec::new());
+        let mut file = UploadedFile::new(Source::Stream(stream), None, 4, None, None).unwrap();
+        assert_eq!(file.get_error(), 4);
+        assert!(file._get_stream().is_err());
+        assert!(file._move_to(&temp_path("upload-error"), &cwd()).is_err());
+    }
+
+    #[test]
+    fn invalid_error_code() {
+        let stream = Stream::from_bytes(Vec::new());
+        assert!(UploadedFile::new(Source::Stream(stream.clone()), None, 5, None, None).is_err());
+        assert!(UploadedFile::new(Source::Stream(stream), None, 9, None, None).is_err());
+    }
+
+    #[test]
+    fn client_metadata() {
+        let stream = Stream::from_bytes(Vec::new());
+        let file = UploadedFile::new(
+            Source::Stream(stream),
+            Some(0),
+            0,
+            Some("photo.jpg".into()),
+            Some("image/jpeg".into()),
+        )
+        .unwrap();
+        assert_eq!(file.get_client_filename().as_deref(), Some("photo.jpg"));
+        assert_eq!(file.get_client_media_type().as_deref(), Some("image/jpeg"));
+        assert_eq!(file.get_size(), Some(0));
+    }
+
+    #[test]
+    fn validate_size() {
+        let stream = Stream::from_bytes(b"1234".to_vec());
+        let file =
+            UploadedFile::new(Source::Stream(stream.clone()), Some(4), 0, None, None).unwrap();
+        assert!(file._validate().is_ok());
+
+        let file =
+            UploadedFile::new(Source::Stream(stream.clone()), Some(10), 0, None, None).unwrap();
+        assert_eq!(file.get_size(), Some(10));
+        assert_eq!(file.get_stream_size(), Some(4));
+        assert_eq!(
+            file._validate().unwrap_err(),
+            "Declared size of 10 bytes does not match the stream size of 4 bytes"
+        );
+
+        let file = UploadedFile::new(Source::Stream(stream), None, 0, None, None).unwrap();
+        assert!(file._validate().is_ok());
+    }
+
+    #[test]
+    fn validate_unsized_stream() {
+        let stream = Stream::from_bytes(b"1234".to_vec());
+        stream.detach();
+        let file = UploadedFile::new(Source::Stream(stream), Some(10), 0, None, None).unwrap();
+        assert_eq!(file.get_stream_size(), None);
+        assert!(file._validate().is_ok());
+    }
+}
@@ -0,0 +1,49 @@
+use crate::class::stream::Stream;
+use crate::class::uploaded_file::UploadedFile;
+use ext_php_rs::prelude::*;
+
+// PSR-17 UploadedFileFactoryInterface.
+#[php_class(name = "Takaram\\Psr7\\Internal\\UploadedFileFactory")]
+pub struct UploadedFileFactory;
+
+#[php_impl]
+impl UploadedFileFactory {
+    pub fn __construct() -> Self {
+        Self
+    }
+
+    /// Without `size`, the size of the stream is used.
+    pub fn create_uploaded_file(
+        &self,
+        stream: &Stream,
+        size: Option<i64>,
+        error: Option<i64>,
+        client_filename: Option<String>,
+        client_media_type: Option<String>,
+    ) -> PhpResult<UploadedFile> {
+        UploadedFile::from_stream(
+            stream,
+            size.or_else(|| stream.get_size()),
+            error.unwrap_or(0),
+            client_filename,
+            client_media_type,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn create_uploaded_file() {
+        let factory = UploadedFileFactory::__construct();
+        let stream = Stream::from_bytes(b"contents".to_vec());
+        let file = factory
+            .create_uploaded_file(&stream, None, None, Some("a.txt".into()), None)
+            .unwrap();
+        assert_eq!(file.get_size(), Some(8));
+        assert_eq!(file.get_error(), 0);
+        assert_eq!(file.get_client_filename().as_deref(), Some("a.txt"));
+    }
+}
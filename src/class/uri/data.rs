@@ -0,0 +1,98 @@
+use super::Uri;
+use crate::util::{base64_decode, percent_decode_bytes};
+
+// RFC 2397, section 2
+const DEFAULT_MEDIA_TYPE: &str = "text/plain;charset=US-ASCII";
+
+impl Uri {
+    // Splits a `data:` URI into its media type, whether the payload is
+    // base64-encoded, and the (still encoded) payload.
+    fn data_parts(&self) -> Result<(&str, bool, &str), &'static str> {
+        if *self.scheme != *"data" {
+            return Err("Not a data URI");
+        }
+        let (media_type, payload) = self
+            .path
+            .split_once(',')
+            .ok_or("Invalid data URI: missing comma")?;
+        let (media_type, base64) = match media_type.strip_suffix(";base64") {
+            Some(media_type) => (media_type, true),
+            None => (media_type, false),
+        };
+        Ok((media_type, base64, payload))
+    }
+
+    /// Returns the media type of a `data:` URI, defaulting to
+    /// `text/plain;charset=US-ASCII`; a lone `;charset=...` keeps the
+    /// default type.
+    pub(super) fn _get_data_media_type(&self) -> Result<String, &'static str> {
+        let (media_type, _, _) = self.data_parts()?;
+        let media_type = String::from_utf8_lossy(&percent_decode_bytes(media_type)).into_owned();
+        Ok(if media_type.is_empty() {
+            DEFAULT_MEDIA_TYPE.to_string()
+        } else if media_type.starts_with(';') {
+            format!("text/plain{media_type}")
+        } else {
+            media_type
+        })
+    }
+
+    pub(super) fn _get_data_payload(&self) -> Result<Vec<u8>, &'static str> {
+        let (_, base64, payload) = self.data_parts()?;
+        let payload = percent_decode_bytes(payload);
+        if base64 {
+            base64_decode(&payload).ok_or("Invalid data URI: malformed base64 payload")
+        } else {
+            Ok(payload)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn media_type() {
+        let cases = [
+            ("data:,Hello", "text/plain;charset=US-ASCII"),
+            ("data:;charset=utf-8,Hello", "text/plain;charset=utf-8"),
+            ("data:image/png;base64,iVBORw0KGgo=", "image/png"),
+            ("data:text/html,%3Ch1%3E", "text/html"),
+        ];
+        for (uri, expected) in cases {
+            let uri = Uri::new(uri).unwrap();
+            assert_eq!(uri._get_data_media_type().unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn payload() {
+        let cases: [(&str, &[u8]); 5] = [
+            ("data:,Hello%2C%20World%21", b"Hello, World!"),
+            (
+                "data:text/plain;base64,SGVsbG8sIFdvcmxkIQ==",
+                b"Hello, World!",
+            ),
+            ("data:;base64,SGVsbG8", b"Hello"),
+            ("data:;base64,AP8%3D", b"\x00\xff"),
+            ("data:,", b""),
+        ];
+        for (input, expected) in cases {
+            let uri = Uri::new(input).unwrap();
+            assert_eq!(uri._get_data_payload().unwrap(), expected, "uri: {input}");
+        }
+    }
+
+    #[test]
+    fn invalid() {
+        for uri in [
+            "http://example.com/",
+            "data:text/plain",
+            "data:;base64,S$==",
+        ] {
+            let uri = Uri::new(uri).unwrap();
+            assert!(uri._get_data_payload().is_err());
+        }
+    }
+}
@@ -0,0 +1,144 @@
+use super::{normalize_host, Uri};
+use crate::util::{encode_path_segment, percent_decode};
+
+impl Uri {
+    /// Converts an absolute local path to a `file` URI (RFC 8089), in the
+    /// canonical `file:///path` form unless it names a UNC host. Paths with
+    /// a drive letter (`C:\dir`) or in UNC form (`\\server\share`) are read
+    /// as Windows paths, where backslashes separate segments.
+    pub(super) fn _from_file_path(path: &str) -> Result<Self, String> {
+        let windows = is_drive_path(path) || path.starts_with("\\\\");
+        let normalized = if windows {
+            path.replace('\\', "/")
+        } else {
+            path.to_string()
+        };
+        let (host, path) = match normalized.strip_prefix("//") {
+            Some(unc) => {
+                let (host, rest) = unc.split_at(unc.find('/').unwrap_or(unc.len()));
+                let host = normalize_host(host)
+                    .filter(|host| !host.is_empty())
+                    .ok_or_else(|| format!("Invalid UNC host in path: {path}"))?;
+                (host, rest.to_string())
+            }
+            None if is_drive_path(&normalized) => (String::new(), format!("/{normalized}")),
+            None if normalized.starts_with('/') => (String::new(), normalized),
+            None => return Err(format!("Path is not absolute: {path}")),
+        };
+        let path = path
+            .split('/')
+            .map(encode_path_segment)
+            .collect::<Vec<_>>()
+            .join("/");
+
+        Ok(Self {
+            scheme: "file".into(),
+            host: host.into(),
+            path: path.into(),
+            ..Self::default()
+        })
+    }
+
+    /// Converts a `file` URI back to a local path; drive letter and UNC
+    /// paths are returned with backslashes.
+    pub(super) fn _to_file_path(&self) -> Result<String, String> {
+        if *self.scheme != *"file" {
+            return Err(format!("Not a file URI: {}", self.to_string()));
+        }
+        let path = percent_decode(&self.path);
+        if !self.host.is_empty() && *self.host != *"localhost" {
+            return Ok(format!("\\\\{}{}", self.host, path.replace('/', "\\")));
+        }
+        match path.strip_prefix('/') {
+            Some(drive_path) if is_drive_path(drive_path) => Ok(drive_path.replace('/', "\\")),
+            _ => Ok(path),
+        }
+    }
+}
+
+// `C:` followed by a separator or nothing.
+fn is_drive_path(path: &str) -> bool {
+    let bytes = path.as_bytes();
+    bytes.len() >= 2
+        && bytes[0].is_ascii_alphabetic()
+        && bytes[1] == b':'
+        && matches!(bytes.get(2), None | Some(b'/' | b'\\'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_file_path() {
+        let cases = [
+            ("/etc/hosts", "file:///etc/hosts"),
+            ("/tmp/a b/100%.txt", "file:///tmp/a%20b/100%25.txt"),
+            ("/tmp/a\\b", "file:///tmp/a%5Cb"),
+            (
+                "C:\\Program Files\\app.exe",
+                "file:///C:/Program%20Files/app.exe",
+            ),
+            ("c:/dir/", "file:///c:/dir/"),
+            (
+                "\\\\Server\\share\\file.txt",
+                "file://server/share/file.txt",
+            ),
+        ];
+        for (path, expected) in cases {
+            let uri = Uri::_from_file_path(path).unwrap();
+            assert_eq!(uri.to_string(), expected, "path: {path}");
+        }
+    }
+
+    #[test]
+    fn from_file_path_reparses() {
+        for path in ["/etc/hosts", "C:\\Windows\\win.ini", "\\\\server\\share"] {
+            let str = Uri::_from_file_path(path).unwrap().to_string();
+            let uri = Uri::new(str.as_str()).unwrap();
+            assert_eq!(uri.to_string(), str, "path: {path}");
+            assert_eq!(uri._to_file_path().unwrap(), path);
+        }
+    }
+
+    #[test]
+    fn from_file_path_relative() {
+        for path in ["relative/path", "C:relative", "\\\\"] {
+            assert!(Uri::_from_file_path(path).is_err(), "path: {path}");
+        }
+    }
+
+    #[test]
+    fn to_file_path() {
+        let cases = [
+            ("file:///etc/hosts", "/etc/hosts"),
+            ("file://localhost/tmp/a%20b", "/tmp/a b"),
+            (
+                "file:///C:/Program%20Files/app.exe",
+                "C:\\Program Files\\app.exe",
+            ),
+            (
+                "file://server/share/file.txt",
+                "\\\\server\\share\\file.txt",
+            ),
+        ];
+        for (uri, expected) in cases {
+            let uri = Uri::new(uri).unwrap();
+            assert_eq!(uri._to_file_path().unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn to_file_path_other_scheme() {
+        let uri = Uri::new("http://example.com/").unwrap();
+        assert!(uri._to_file_path().is_err());
+    }
+
+    #[test]
+    fn round_trip() {
+        for path in ["/tmp/a b/c#d?e", "D:\\data\\x%y", "\\\\host\\share\\dir\\f"] {
+            let uri = Uri::_from_file_path(path).unwrap();
+            assert_eq!(uri._to_file_path().unwrap(), path);
+        }
+    }
+}
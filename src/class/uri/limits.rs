@@ -0,0 +1,118 @@
+use super::Uri;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+pub(crate) const DEFAULT_MAX_LENGTH: usize = 1024 * 1024;
+
+static MAX_LENGTH: AtomicUsize = AtomicUsize::new(DEFAULT_MAX_LENGTH);
+static MAX_COMPONENT_LENGTH: AtomicUsize = AtomicUsize::new(0);
+
+// Upper bounds, in bytes, on parsed URIs and on each of their components,
+// so oversized input is rejected before it reaches the parser. Zero means
+// no limit.
+#[derive(Clone, Copy)]
+pub(crate) struct Limits {
+    pub(crate) length: usize,
+    pub(crate) component_length: usize,
+}
+
+impl Limits {
+    pub(crate) fn set_default(self) {
+        MAX_LENGTH.store(self.length, Ordering::Relaxed);
+        MAX_COMPONENT_LENGTH.store(self.component_length, Ordering::Relaxed);
+    }
+
+    pub(super) fn configured() -> Self {
+        Self {
+            length: MAX_LENGTH.load(Ordering::Relaxed),
+            component_length: MAX_COMPONENT_LENGTH.load(Ordering::Relaxed),
+        }
+    }
+
+    pub(super) fn check_length(&self, str: &str) -> Result<(), String> {
+        if exceeds(str.len(), self.length) {
+            return Err(format!(
+                "URI of {} bytes exceeds the maximum length of {} bytes",
+                str.len(),
+                self.length
+            ));
+        }
+        Ok(())
+    }
+
+    pub(super) fn check_components(&self, uri: &Uri) -> Result<(), String> {
+        let components = [
+            ("user info", &*uri.user_info),
+            ("host", &uri.host),
+            ("path", &uri.path),
+            ("query", &uri.query),
+            ("fragment", uri.fragment.as_deref().unwrap_or("")),
+        ];
+        match components
+            .iter()
+            .find(|(_, value)| exceeds(value.len(), self.component_length))
+        {
+            Some((name, value)) => Err(format!(
+                "URI {name} of {} bytes exceeds the maximum component length of {} bytes",
+                value.len(),
+                self.component_length
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+fn exceeds(len: usize, limit: usize) -> bool {
+    limit != 0 && len > limit
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_length() {
+        let limits = Limits {
+            length: 20,
+            component_length: 0,
+        };
+        assert!(limits.check_length("http://example.com/").is_ok());
+        assert_eq!(
+            limits.check_length("http://example.com/path"),
+            Err("URI of 23 bytes exceeds the maximum length of 20 bytes".to_string())
+        );
+    }
+
+    #[test]
+    fn check_components() {
+        let limits = Limits {
+            length: 0,
+            component_length: 5,
+        };
+        let uri = Uri::new("http://host/path?query#frag").unwrap();
+        assert!(limits.check_components(&uri).is_ok());
+        let uri = Uri::new("http://host/path?query=1").unwrap();
+        assert_eq!(
+            limits.check_components(&uri),
+            Err("URI query of 7 bytes exceeds the maximum component length of 5 bytes".to_string())
+        );
+    }
+
+    #[test]
+    fn parse_rejects_oversized_input() {
+        let str = format!("http://example.com/{}", "a".repeat(DEFAULT_MAX_LENGTH));
+        assert!(Uri::new(str).is_err());
+    }
+
+    #[test]
+    fn zero_means_no_limit() {
+        let limits = Limits {
+            length: 0,
+            component_length: 0,
+        };
+        let str = format!("http://example.com/{}", "a".repeat(DEFAULT_MAX_LENGTH));
+        assert!(limits.check_length(&str).is_ok());
+        assert!(limits
+            .check_components(&Uri::new(&str[..100]).unwrap())
+            .is_ok());
+    }
+}
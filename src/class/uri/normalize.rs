@@ -0,0 +1,161 @@
+use super::{default_port, Uri};
+use crate::util::{capitalize_percent_encoding, decode_unreserved, remove_dot_segments};
+
+pub(crate) const CAPITALIZE_PERCENT_ENCODING: i64 = 1;
+pub(crate) const DECODE_UNRESERVED_CHARACTERS: i64 = 2;
+pub(crate) const CONVERT_EMPTY_PATH: i64 = 4;
+pub(crate) const REMOVE_DEFAULT_HOST: i64 = 8;
+pub(crate) const REMOVE_DEFAULT_PORT: i64 = 16;
+pub(crate) const REMOVE_DOT_SEGMENTS: i64 = 32;
+pub(crate) const REMOVE_DUPLICATE_SLASHES: i64 = 64;
+pub(crate) const SORT_QUERY_PARAMETERS: i64 = 128;
+
+// Normalizations that never change the resource the URI identifies.
+pub(crate) const PRESERVING_NORMALIZATIONS: i64 = CAPITALIZE_PERCENT_ENCODING
+    | DECODE_UNRESERVED_CHARACTERS
+    | CONVERT_EMPTY_PATH
+    | REMOVE_DEFAULT_HOST
+    | REMOVE_DEFAULT_PORT
+    | REMOVE_DOT_SEGMENTS;
+
+impl Uri {
+    /// Applies the normalizations selected by `flags`, mirroring guzzle's
+    /// `UriNormalizer`. Unlike guzzle, the percent-encoding ones also cover
+    /// the user info, host and fragment.
+    pub(crate) fn normalize_with(&self, flags: i64) -> Uri {
+        let mut uri = self.clone();
+        if flags & CAPITALIZE_PERCENT_ENCODING != 0 {
+            uri.map_encoded(capitalize_percent_encoding);
+        }
+        if flags & DECODE_UNRESERVED_CHARACTERS != 0 {
+            uri.map_encoded(decode_unreserved);
+        }
+        if flags & CONVERT_EMPTY_PATH != 0
+            && uri.path.is_empty()
+            && matches!(uri.scheme.as_str(), "http" | "https")
+        {
+            uri.path = "/".into();
+        }
+        if flags & REMOVE_DEFAULT_HOST != 0 && uri.scheme == "file" && uri.host == "localhost" {
+            uri.host = Default::default();
+        }
+        if flags & REMOVE_DEFAULT_PORT != 0 && uri.port == default_port(&uri.scheme) {
+            uri.port = None;
+        }
+        // In a relative reference, dot segments are only meaningful once it
+        // is resolved against a base (RFC 3986, section 5.2).
+        if flags & REMOVE_DOT_SEGMENTS != 0 && (!uri.scheme.is_empty() || !uri.host.is_empty()) {
+            uri.path = remove_dot_segments(&uri.path).into();
+        }
+        if flags & REMOVE_DUPLICATE_SLASHES != 0 {
+            uri.path = remove_duplicate_slashes(&uri.path).into();
+        }
+        if flags & SORT_QUERY_PARAMETERS != 0 && !uri.query.is_empty() {
+            let mut pairs: Vec<_> = uri.query.split('&').collect();
+            pairs.sort_unstable();
+            uri.query = pairs.join("&").into();
+        }
+        uri
+    }
+
+    // Every component that may hold percent-encoded octets.
+    fn map_encoded(&mut self, f: fn(&str) -> String) {
+        self.user_info = f(&self.user_info).into();
+        self.host = f(&self.host.to_lowercase()).into();
+        self.path = f(&self.path).into();
+        self.query = f(&self.query).into();
+        self.fragment = self.fragment.as_deref().map(|fragment| f(fragment).into());
+    }
+}
+
+fn remove_duplicate_slashes(path: &str) -> String {
+    let mut result = String::with_capacity(path.len());
+    for c in path.chars() {
+        if c != '/' || !result.ends_with('/') {
+            result.push(c);
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn normalize(str: &str, flags: i64) -> String {
+        Uri::new(str).unwrap().normalize_with(flags).to_string()
+    }
+
+    #[test]
+    fn each_flag() {
+        let cases = [
+            (
+                "http://example.com/a%c3%a9?b=%2f",
+                CAPITALIZE_PERCENT_ENCODING,
+                "http://example.com/a%C3%A9?b=%2F",
+            ),
+            (
+                "http://example.com/%7euser?%61=b",
+                DECODE_UNRESERVED_CHARACTERS,
+                "http://example.com/~user?a=b",
+            ),
+            (
+                "http://example.com",
+                CONVERT_EMPTY_PATH,
+                "http://example.com/",
+            ),
+            (
+                "file://localhost/etc/hosts",
+                REMOVE_DEFAULT_HOST,
+                "file:///etc/hosts",
+            ),
+            (
+                "https://example.com:443/",
+                REMOVE_DEFAULT_PORT,
+                "https://example.com/",
+            ),
+            (
+                "http://example.com/a/./b/../c",
+                REMOVE_DOT_SEGMENTS,
+                "http://example.com/a/c",
+            ),
+            (
+                "http://example.com//a///b/",
+                REMOVE_DUPLICATE_SLASHES,
+                "http://example.com/a/b/",
+            ),
+            (
+                "http://example.com/?c=3&a=1&b=2",
+                SORT_QUERY_PARAMETERS,
+                "http://example.com/?a=1&b=2&c=3",
+            ),
+        ];
+        for (input, flag, expected) in cases {
+            assert_eq!(normalize(input, flag), expected, "flag: {flag}");
+            assert_eq!(normalize(input, 0), Uri::new(input).unwrap().to_string());
+        }
+    }
+
+    #[test]
+    fn preserving_normalizations() {
+        assert_eq!(
+            normalize(
+                "http://example.com:80/a/../%7e%2f?q=%3a",
+                PRESERVING_NORMALIZATIONS
+            ),
+            "http://example.com/~%2F?q=%3A"
+        );
+    }
+
+    #[test]
+    fn remove_dot_segments_keeps_relative_references() {
+        assert_eq!(normalize("../a/./b", REMOVE_DOT_SEGMENTS), "../a/./b");
+        assert_eq!(normalize("/a/../b", REMOVE_DOT_SEGMENTS), "/a/../b");
+        assert_eq!(normalize("urn:a/./b", REMOVE_DOT_SEGMENTS), "urn:a/b");
+    }
+
+    #[test]
+    fn convert_empty_path_only_for_http() {
+        assert_eq!(normalize("urn:", CONVERT_EMPTY_PATH), "urn:");
+    }
+}
@@ -0,0 +1,226 @@
+use super::{ParseMode, Uri};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::mem;
+use std::sync::Arc;
+
+const NIL: usize = usize::MAX;
+
+thread_local! {
+    // Each thread serves one request at a time, so the cache needs no lock
+    // and its size can be reset when the request ends.
+    static PARSE_CACHE: RefCell<ParseCache> = RefCell::new(ParseCache::new());
+}
+
+pub(super) fn with<T>(f: impl FnOnce(&mut ParseCache) -> T) -> T {
+    PARSE_CACHE.with_borrow_mut(f)
+}
+
+struct Entry {
+    key: Arc<str>,
+    mode: ParseMode,
+    uri: Uri,
+    prev: usize,
+    next: usize,
+}
+
+// Least-recently-used cache of parsed URIs keyed by the input string and
+// the mode it was parsed in. Entries form a doubly linked list from the
+// most to the least recently used, indexed by one hash map per mode. A
+// capacity of zero disables it.
+pub(super) struct ParseCache {
+    capacity: usize,
+    index: [HashMap<Arc<str>, usize>; 3],
+    entries: Vec<Entry>,
+    head: usize,
+    tail: usize,
+}
+
+impl ParseCache {
+    pub(super) fn new() -> Self {
+        Self {
+            capacity: 0,
+            index: Default::default(),
+            entries: Vec::new(),
+            head: NIL,
+            tail: NIL,
+        }
+    }
+
+    pub(super) fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Keeps the `capacity` most recently used entries.
+    pub(super) fn resize(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        if self.entries.len() <= capacity {
+            return;
+        }
+        let mut order = Vec::with_capacity(capacity);
+        let mut i = self.head;
+        while order.len() < capacity {
+            order.push(i);
+            i = self.entries[i].next;
+        }
+        let mut entries: Vec<_> = mem::take(&mut self.entries).into_iter().map(Some).collect();
+        self.index.iter_mut().for_each(HashMap::clear);
+        self.head = NIL;
+        self.tail = NIL;
+        for i in order.into_iter().rev() {
+            if let Some(entry) = entries[i].take() {
+                self.push(entry.key, entry.mode, entry.uri);
+            }
+        }
+    }
+
+    pub(super) fn get(&mut self, str: &str, mode: ParseMode) -> Option<Uri> {
+        let i = *self.index[mode as usize].get(str)?;
+        self.unlink(i);
+        self.link_front(i);
+        Some(self.entries[i].uri.clone())
+    }
+
+    pub(super) fn insert(&mut self, str: &str, mode: ParseMode, uri: &Uri) {
+        if self.capacity == 0 {
+            return;
+        }
+        if let Some(&i) = self.index[mode as usize].get(str) {
+            self.entries[i].uri = uri.clone();
+            self.unlink(i);
+            self.link_front(i);
+            return;
+        }
+        if self.entries.len() < self.capacity {
+            self.push(Arc::from(str), mode, uri.clone());
+            return;
+        }
+        // Reuse the slot of the least recently used entry.
+        let i = self.tail;
+        self.unlink(i);
+        let entry = &mut self.entries[i];
+        self.index[entry.mode as usize].remove(&entry.key);
+        entry.key = Arc::from(str);
+        entry.mode = mode;
+        entry.uri = uri.clone();
+        self.index[mode as usize].insert(entry.key.clone(), i);
+        self.link_front(i);
+    }
+
+    fn push(&mut self, key: Arc<str>, mode: ParseMode, uri: Uri) {
+        let i = self.entries.len();
+        self.index[mode as usize].insert(key.clone(), i);
+        self.entries.push(Entry {
+            key,
+            mode,
+            uri,
+            prev: NIL,
+            next: NIL,
+        });
+        self.link_front(i);
+    }
+
+    fn unlink(&mut self, i: usize) {
+        let Entry { prev, next, .. } = self.entries[i];
+        match prev {
+            NIL => self.head = next,
+            prev => self.entries[prev].next = next,
+        }
+        match next {
+            NIL => self.tail = prev,
+            next => self.entries[next].prev = prev,
+        }
+    }
+
+    fn link_front(&mut self, i: usize) {
+        self.entries[i].prev = NIL;
+        self.entries[i].next = self.head;
+        match self.head {
+            NIL => self.tail = i,
+            head => self.entries[head].prev = i,
+        }
+        self.head = i;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn insert(cache: &mut ParseCache, str: &str) {
+        cache.insert(str, ParseMode::Standard, &Uri::new(str).unwrap());
+    }
+
+    #[test]
+    fn disabled_by_default() {
+        let mut cache = ParseCache::new();
+        insert(&mut cache, "http://example.com/");
+        assert!(cache
+            .get("http://example.com/", ParseMode::Standard)
+            .is_none());
+    }
+
+    #[test]
+    fn get_returns_cached_uri() {
+        let mut cache = ParseCache::new();
+        cache.resize(2);
+        insert(&mut cache, "http://example.com/a?b#c");
+        let uri = cache
+            .get("http://example.com/a?b#c", ParseMode::Standard)
+            .unwrap();
+        assert_eq!(uri.to_string(), "http://example.com/a?b#c");
+        assert!(cache
+            .get("http://example.com/a?b#c", ParseMode::Strict)
+            .is_none());
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let mut cache = ParseCache::new();
+        cache.resize(2);
+        insert(&mut cache, "/a");
+        insert(&mut cache, "/b");
+        cache.get("/a", ParseMode::Standard);
+        insert(&mut cache, "/c");
+        assert!(cache.get("/a", ParseMode::Standard).is_some());
+        assert!(cache.get("/b", ParseMode::Standard).is_none());
+        assert!(cache.get("/c", ParseMode::Standard).is_some());
+    }
+
+    #[test]
+    fn insert_existing_key() {
+        let mut cache = ParseCache::new();
+        cache.resize(2);
+        insert(&mut cache, "/a");
+        insert(&mut cache, "/b");
+        insert(&mut cache, "/a");
+        insert(&mut cache, "/c");
+        assert!(cache.get("/a", ParseMode::Standard).is_some());
+        assert!(cache.get("/b", ParseMode::Standard).is_none());
+    }
+
+    #[test]
+    fn resize_keeps_most_recently_used() {
+        let mut cache = ParseCache::new();
+        cache.resize(3);
+        insert(&mut cache, "/a");
+        insert(&mut cache, "/b");
+        insert(&mut cache, "/c");
+        cache.get("/a", ParseMode::Standard);
+        cache.resize(2);
+        assert!(cache.get("/b", ParseMode::Standard).is_none());
+        insert(&mut cache, "/d");
+        assert!(cache.get("/c", ParseMode::Standard).is_none());
+        assert!(cache.get("/a", ParseMode::Standard).is_some());
+        assert!(cache.get("/d", ParseMode::Standard).is_some());
+    }
+
+    #[test]
+    fn resize_drops_entries() {
+        let mut cache = ParseCache::new();
+        cache.resize(2);
+        insert(&mut cache, "/a");
+        cache.resize(0);
+        assert!(cache.get("/a", ParseMode::Standard).is_none());
+    }
+}
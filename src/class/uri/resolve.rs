@@ -0,0 +1,296 @@
+use super::Uri;
+use crate::util::remove_dot_segments;
+
+impl Uri {
+    /// Resolves `reference` against `self` as the base URI (RFC 3986,
+    /// section 5.2.2).
+    pub(crate) fn resolve(&self, reference: &Uri) -> Uri {
+        if !reference.scheme.is_empty() {
+            return Self {
+                path: remove_dot_segments(&reference.path).into(),
+                ..reference.clone()
+            };
+        }
+        if !reference.host.is_empty() {
+            return Self {
+                scheme: self.scheme.clone(),
+                path: remove_dot_segments(&reference.path).into(),
+                ..reference.clone()
+            };
+        }
+
+        let (path, query) = if reference.path.is_empty() {
+            let query = if reference.query.is_empty() {
+                &self.query
+            } else {
+                &reference.query
+            };
+            (self.path.clone(), query.clone())
+        } else if reference.path.starts_with('/') {
+            (
+                remove_dot_segments(&reference.path).into(),
+                reference.query.clone(),
+            )
+        } else {
+            (
+                remove_dot_segments(&self.merge_path(&reference.path)).into(),
+                reference.query.clone(),
+            )
+        };
+        Self {
+            scheme: self.scheme.clone(),
+            user_info: self.user_info.clone(),
+            host: self.host.clone(),
+            port: self.port,
+            path,
+            query,
+            fragment: reference.fragment.clone(),
+        }
+    }
+
+    /// Returns the shortest reference that resolves against `self` to
+    /// `target`, or `target` itself when no relative form exists.
+    pub(crate) fn relativize(&self, target: &Uri) -> Uri {
+        if !target.scheme.is_empty()
+            && (self.scheme != target.scheme || (target.host.is_empty() && !self.host.is_empty()))
+        {
+            return target.clone();
+        }
+        if Uri::is_relative_path_reference(target) {
+            return target.clone();
+        }
+        if !target.host.is_empty() && self.get_authority() != target.get_authority() {
+            return Self {
+                scheme: Default::default(),
+                ..target.clone()
+            };
+        }
+
+        let (path, query) = if self.path != target.path {
+            (
+                self.relative_path(&target.path).into(),
+                target.query.clone(),
+            )
+        } else if self.query == target.query {
+            (Default::default(), Default::default())
+        } else if target.query.is_empty() {
+            // As in `relative_path`, a colon would make the segment a scheme.
+            let last_segment = target.path.rsplit('/').next().unwrap_or("");
+            let path = if last_segment.is_empty() || last_segment.contains(':') {
+                format!("./{last_segment}")
+            } else {
+                last_segment.to_string()
+            };
+            (path.into(), Default::default())
+        } else {
+            (Default::default(), target.query.clone())
+        };
+        Self {
+            path,
+            query,
+            fragment: target.fragment.clone(),
+            ..Self::default()
+        }
+    }
+
+    fn relative_path(&self, target_path: &str) -> String {
+        let mut base_segments: Vec<_> = self.path.split('/').collect();
+        let mut target_segments: Vec<_> = target_path.split('/').collect();
+        base_segments.pop();
+        let last_segment = target_segments.pop().unwrap_or("");
+        let common = base_segments
+            .iter()
+            .zip(&target_segments)
+            .take_while(|(base, target)| base == target)
+            .count();
+        let mut segments = target_segments.split_off(common);
+        segments.push(last_segment);
+        let path = format!(
+            "{}{}",
+            "../".repeat(base_segments.len() - common),
+            segments.join("/")
+        );
+
+        // An empty path, or a first segment that is empty or contains a
+        // colon, would be read differently; "./" keeps it a relative path.
+        if path.is_empty()
+            || path
+                .split('/')
+                .next()
+                .is_some_and(|segment| segment.contains(':'))
+        {
+            format!("./{path}")
+        } else if path.starts_with('/') {
+            // Resolving against an authority with an empty path adds the
+            // leading slash by itself.
+            if !self.host.is_empty() && self.path.is_empty() {
+                format!(".{path}")
+            } else {
+                format!("./{path}")
+            }
+        } else {
+            path
+        }
+    }
+
+    // RFC 3986, section 5.2.3
+    fn merge_path(&self, path: &str) -> String {
+        if !self.host.is_empty() && self.path.is_empty() {
+            return format!("/{path}");
+        }
+        match self.path.rfind('/') {
+            Some(pos) => format!("{}{}", &self.path[..=pos], path),
+            None => path.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_resolves(cases: &[(&str, &str)]) {
+        let base = Uri::new("http://a/b/c/d;p?q").unwrap();
+        for (reference, expected) in cases {
+            let reference = Uri::new(*reference).unwrap();
+            assert_eq!(
+                base.resolve(&reference).to_string(),
+                *expected,
+                "reference: {}",
+                reference.to_string()
+            );
+        }
+    }
+
+    // RFC 3986, section 5.4.1
+    #[test]
+    fn resolve_normal_examples() {
+        assert_resolves(&[
+            ("g:h", "g:h"),
+            ("g", "http://a/b/c/g"),
+            ("./g", "http://a/b/c/g"),
+            ("g/", "http://a/b/c/g/"),
+            ("/g", "http://a/g"),
+            ("//g", "http://g"),
+            ("?y", "http://a/b/c/d;p?y"),
+            ("g?y", "http://a/b/c/g?y"),
+            ("#s", "http://a/b/c/d;p?q#s"),
+            ("g#s", "http://a/b/c/g#s"),
+            ("g?y#s", "http://a/b/c/g?y#s"),
+            (";x", "http://a/b/c/;x"),
+            ("g;x", "http://a/b/c/g;x"),
+            ("g;x?y#s", "http://a/b/c/g;x?y#s"),
+            ("", "http://a/b/c/d;p?q"),
+            (".", "http://a/b/c/"),
+            ("./", "http://a/b/c/"),
+            ("..", "http://a/b/"),
+            ("../", "http://a/b/"),
+            ("../g", "http://a/b/g"),
+            ("../..", "http://a/"),
+            ("../../", "http://a/"),
+            ("../../g", "http://a/g"),
+        ]);
+    }
+
+    // RFC 3986, section 5.4.2
+    #[test]
+    fn resolve_abnormal_examples() {
+        assert_resolves(&[
+            ("../../../g", "http://a/g"),
+            ("../../../../g", "http://a/g"),
+            ("/./g", "http://a/g"),
+            ("/../g", "http://a/g"),
+            ("g.", "http://a/b/c/g."),
+            (".g", "http://a/b/c/.g"),
+            ("g..", "http://a/b/c/g.."),
+            ("..g", "http://a/b/c/..g"),
+            ("./../g", "http://a/b/g"),
+            ("./g/.", "http://a/b/c/g/"),
+            ("g/./h", "http://a/b/c/g/h"),
+            ("g/../h", "http://a/b/c/h"),
+            ("g;x=1/./y", "http://a/b/c/g;x=1/y"),
+            ("g;x=1/../y", "http://a/b/c/y"),
+            ("g?y/./x", "http://a/b/c/g?y/./x"),
+            ("g?y/../x", "http://a/b/c/g?y/../x"),
+            ("g#s/./x", "http://a/b/c/g#s/./x"),
+            ("g#s/../x", "http://a/b/c/g#s/../x"),
+        ]);
+    }
+
+    #[test]
+    fn relativize() {
+        let cases = [
+            ("http://a/b/c/g", "g"),
+            ("http://a/b/c/g/", "g/"),
+            ("http://a/b/c/d;p?y", "?y"),
+            ("http://a/b/c/g?y", "g?y"),
+            ("http://a/b/c/d;p?q#s", "#s"),
+            ("http://a/b/c/g#s", "g#s"),
+            ("http://a/b/c/;x", ";x"),
+            ("http://a/b/c/d;p", "d;p"),
+            ("http://a/b/c/", "./"),
+            ("http://a/b/", "../"),
+            ("http://a/b/g", "../g"),
+            ("http://a/", "../../"),
+            ("http://a/g", "../../g"),
+            ("http://a/b/c/g:h", "./g:h"),
+            ("http://g/x", "//g/x"),
+            ("https://a/b", "https://a/b"),
+            ("g:h", "g:h"),
+            ("../x", "../x"),
+        ]
+        .map(|(target, expected)| ("http://a/b/c/d;p?q", target, expected));
+        let query_dropped = [
+            ("http://a/b/c:d?q", "http://a/b/c:d", "./c:d"),
+            ("http://a/b/c?q", "http://a/b/c", "c"),
+        ];
+        for (base, target, expected) in cases.into_iter().chain(query_dropped) {
+            let base = Uri::new(base).unwrap();
+            let target = Uri::new(target).unwrap();
+            let relative = base.relativize(&target);
+            assert_eq!(
+                relative.to_string(),
+                expected,
+                "target: {}",
+                target.to_string()
+            );
+            if target.get_scheme() == "http" {
+                assert_eq!(base.resolve(&relative).to_string(), target.to_string());
+            }
+        }
+    }
+
+    #[test]
+    fn relativize_against_empty_base_path() {
+        let base = Uri::new("http://example.com").unwrap();
+        let target = Uri::new("http://example.com/a/b").unwrap();
+        let relative = base.relativize(&target);
+        assert_eq!(relative.to_string(), "./a/b");
+        assert_eq!(
+            base.resolve(&relative).to_string(),
+            "http://example.com/a/b"
+        );
+    }
+
+    #[test]
+    fn resolve_against_empty_base_path() {
+        let base = Uri::new("http://example.com").unwrap();
+        let reference = Uri::new("g").unwrap();
+        assert_eq!(base.resolve(&reference).to_string(), "http://example.com/g");
+    }
+
+    // Same-document references keep the base path, and a fragment-only
+    // one its query too (RFC 3986, section 5.2.2).
+    #[test]
+    fn resolve_fragment_and_query_only() {
+        let base = Uri::new("http://host/a/b?old#x").unwrap();
+        for (reference, expected) in [
+            ("#top", "http://host/a/b?old#top"),
+            ("?page=2", "http://host/a/b?page=2"),
+            ("?page=2#top", "http://host/a/b?page=2#top"),
+        ] {
+            let reference = Uri::new(reference).unwrap();
+            assert_eq!(base.resolve(&reference).to_string(), expected);
+        }
+    }
+}
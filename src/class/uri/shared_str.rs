@@ -0,0 +1,155 @@
+use std::fmt;
+use std::ops::Deref;
+use std::sync::{Arc, OnceLock};
+
+static EMPTY: OnceLock<Arc<str>> = OnceLock::new();
+
+// An immutable string that may be a slice of a larger shared buffer. A
+// parsed URI keeps its original string once, and each component that
+// appears verbatim in it is stored as a range of that buffer rather than
+// as a separate allocation. Clones only bump a reference count.
+//
+// Components are only copied out when they are read, and `Uri::to_string`
+// hands back the original itself when it is already in canonical form.
+// Parsing and validation still happen up front, since the constructor must
+// reject invalid URIs. See `benches/uri.rs` for the parse and read-back
+// costs.
+#[derive(Clone)]
+pub(crate) struct SharedStr {
+    buf: Arc<str>,
+    start: usize,
+    end: usize,
+}
+
+impl SharedStr {
+    /// Returns `part` as a slice of `source` when it occurs there, and as a
+    /// copy otherwise (e.g. after normalization changed it).
+    pub(crate) fn within(source: &Arc<str>, part: &str) -> Self {
+        match offset_in(source, part).or_else(|| source.find(part)) {
+            Some(start) => Self {
+                buf: source.clone(),
+                start,
+                end: start + part.len(),
+            },
+            None => Self::from(part),
+        }
+    }
+
+    pub(crate) fn as_str(&self) -> &str {
+        &self.buf[self.start..self.end]
+    }
+
+    /// The buffer this is a slice of, and where it starts and ends there.
+    pub(crate) fn span(&self) -> (&Arc<str>, usize, usize) {
+        (&self.buf, self.start, self.end)
+    }
+}
+
+// The offset of `part` if it already points into `source`.
+fn offset_in(source: &str, part: &str) -> Option<usize> {
+    let start = (part.as_ptr() as usize).checked_sub(source.as_ptr() as usize)?;
+    (start + part.len() <= source.len() && source.is_char_boundary(start)).then_some(start)
+}
+
+impl Deref for SharedStr {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+// Empty components are common, so they all share one buffer.
+impl Default for SharedStr {
+    fn default() -> Self {
+        Self {
+            buf: EMPTY.get_or_init(|| Arc::from("")).clone(),
+            start: 0,
+            end: 0,
+        }
+    }
+}
+
+impl From<&str> for SharedStr {
+    fn from(str: &str) -> Self {
+        if str.is_empty() {
+            return Self::default();
+        }
+        Self {
+            buf: Arc::from(str),
+            start: 0,
+            end: str.len(),
+        }
+    }
+}
+
+impl From<String> for SharedStr {
+    fn from(str: String) -> Self {
+        if str.is_empty() {
+            return Self::default();
+        }
+        let end = str.len();
+        Self {
+            buf: Arc::from(str),
+            start: 0,
+            end,
+        }
+    }
+}
+
+impl PartialEq for SharedStr {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for SharedStr {}
+
+impl PartialEq<str> for SharedStr {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for SharedStr {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl fmt::Debug for SharedStr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for SharedStr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn within_shares_the_source() {
+        let source: Arc<str> = Arc::from("http://example.com/path");
+        let path = SharedStr::within(&source, &source[18..]);
+        assert_eq!(path, "/path");
+        assert!(Arc::ptr_eq(&path.buf, &source));
+
+        let host = SharedStr::within(&source, &"EXAMPLE.com".to_lowercase());
+        assert_eq!(host, "example.com");
+        assert!(Arc::ptr_eq(&host.buf, &source));
+    }
+
+    #[test]
+    fn within_copies_missing_parts() {
+        let source: Arc<str> = Arc::from("http://EXAMPLE.com/");
+        let host = SharedStr::within(&source, "example.com");
+        assert_eq!(host, "example.com");
+        assert!(!Arc::ptr_eq(&host.buf, &source));
+    }
+}
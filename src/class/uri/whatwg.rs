@@ -0,0 +1,256 @@
+use super::{default_port, is_ip_literal, is_valid_scheme, normalize_host, ParseMode, Uri};
+use crate::util::{
+    percent_decode, percent_encode, remove_dot_segments, FRAGMENT_CHARS, PATH_CHARS, QUERY_CHARS,
+    USER_INFO_CHARS,
+};
+
+const SPECIAL_SCHEMES: &[&str] = &["ftp", "file", "http", "https", "ws", "wss"];
+
+// Code points that cannot appear in the host of a special URL.
+const FORBIDDEN_HOST_CHARS: &[char] = &[
+    ' ', '#', '%', '/', ':', '<', '>', '?', '@', '[', '\\', ']', '^', '|',
+];
+
+impl Uri {
+    /// Parses `url` the way browsers do (WHATWG URL Standard): surrounding
+    /// whitespace and embedded tabs and newlines are dropped, backslashes
+    /// act as slashes in special URLs, missing slashes after the scheme are
+    /// tolerated, and hosts are decoded and converted to punycode. Unlike
+    /// browsers, hosts get no UTS #46 mapping or normalization beyond
+    /// lowercasing (see `idn::to_ascii`).
+    pub(super) fn _from_whatwg(url: &str, base: Option<&str>) -> Result<Self, String> {
+        let base = base
+            .map(|base| Self::_from_whatwg(base, None))
+            .transpose()?;
+        let input: String = url
+            .trim_matches(|c: char| c <= ' ')
+            .chars()
+            .filter(|c| !matches!(c, '\t' | '\n' | '\r'))
+            .collect();
+        let parse = |str: &str| Self::parse(str, ParseMode::Standard);
+        let scheme = input
+            .split_once(':')
+            .map(|(scheme, _)| scheme)
+            .filter(|scheme| is_valid_scheme(scheme))
+            .map(str::to_ascii_lowercase);
+
+        let uri = match (scheme, base) {
+            (Some(scheme), base) if is_special(&scheme) => {
+                let rest = slashes(&input[scheme.len() + 1..]);
+                match base {
+                    // `http:foo` is relative to a base of the same scheme.
+                    Some(base) if *base.scheme == *scheme && !rest.starts_with('/') => {
+                        base.resolve(&parse(&rest)?)
+                    }
+                    _ if scheme == "file" && rest.starts_with("//") => {
+                        parse(&format!("file:{rest}"))?
+                    }
+                    _ if scheme == "file" => {
+                        parse(&format!("file:///{}", rest.trim_start_matches('/')))?
+                    }
+                    _ => parse(&format!("{}://{}", scheme, rest.trim_start_matches('/')))?,
+                }
+            }
+            (Some(_), _) => parse(&input)?,
+            (None, Some(base)) if is_special(&base.scheme) => {
+                base.resolve(&parse(&slashes(&input))?)
+            }
+            (None, Some(base)) => base.resolve(&parse(&input)?),
+            (None, None) => return Err(format!("Invalid URL without a base: {url}")),
+        };
+        uri.whatwg_normalized()
+            .ok_or_else(|| format!("Invalid URL: {url}"))
+    }
+
+    fn whatwg_normalized(&self) -> Option<Self> {
+        let special = is_special(&self.scheme);
+        let mut host = self.host.to_string();
+        if special && !is_ip_literal(&host) {
+            host = normalize_host(&percent_decode(&host))?;
+            if host.contains(FORBIDDEN_HOST_CHARS) {
+                return None;
+            }
+            if ends_in_number(&host) {
+                host = parse_ipv4(&host)?;
+            }
+        }
+        if *self.scheme == *"file" && host == "localhost" {
+            host = String::new();
+        }
+        if special && host.is_empty() && *self.scheme != *"file" {
+            return None;
+        }
+        let mut path = percent_encode(&self.path, PATH_CHARS);
+        if path.starts_with('/') {
+            let segments: Vec<_> = path.split('/').map(decode_dot_segment).collect();
+            path = remove_dot_segments(&segments.join("/"));
+        }
+        if special && path.is_empty() {
+            path = "/".to_string();
+        }
+
+        Some(Self {
+            scheme: self.scheme.clone(),
+            user_info: percent_encode(&self.user_info, USER_INFO_CHARS).into(),
+            host: host.into(),
+            port: self
+                .port
+                .filter(|&port| default_port(&self.scheme) != Some(port)),
+            path: path.into(),
+            query: percent_encode(&self.query, QUERY_CHARS).into(),
+            fragment: self
+                .fragment
+                .as_deref()
+                .map(|fragment| percent_encode(fragment, FRAGMENT_CHARS).into()),
+        })
+    }
+}
+
+fn is_special(scheme: &str) -> bool {
+    SPECIAL_SCHEMES.contains(&scheme)
+}
+
+// Backslashes are path separators in special URLs, up to the query.
+fn slashes(str: &str) -> String {
+    let end = str.find(['?', '#']).unwrap_or(str.len());
+    format!("{}{}", str[..end].replace('\\', "/"), &str[end..])
+}
+
+fn decode_dot_segment(segment: &str) -> &str {
+    match segment.to_ascii_lowercase().as_str() {
+        "%2e" => ".",
+        "%2e%2e" | ".%2e" | "%2e." => "..",
+        _ => segment,
+    }
+}
+
+fn ends_in_number(host: &str) -> bool {
+    let host = host.strip_suffix('.').unwrap_or(host);
+    let last = host.rsplit('.').next().unwrap_or("");
+    !last.is_empty()
+        && (last.bytes().all(|byte| byte.is_ascii_digit())
+            || last
+                .strip_prefix("0x")
+                .is_some_and(|hex| hex.bytes().all(|byte| byte.is_ascii_hexdigit())))
+}
+
+// The WHATWG IPv4 parser, which accepts one to four parts in decimal,
+// octal (leading zero) or hexadecimal (`0x`), e.g. `0x7f.1`.
+fn parse_ipv4(host: &str) -> Option<String> {
+    let host = host.strip_suffix('.').unwrap_or(host);
+    let parts = host
+        .split('.')
+        .map(|part| match part {
+            "" => None,
+            "0x" => Some(0),
+            _ => match part.strip_prefix("0x") {
+                Some(hex) => u64::from_str_radix(hex, 16).ok(),
+                None if part.len() > 1 && part.starts_with('0') => {
+                    u64::from_str_radix(&part[1..], 8).ok()
+                }
+                None => part.parse().ok(),
+            },
+        })
+        .collect::<Option<Vec<u64>>>()?;
+    let (last, init) = parts.split_last()?;
+    if parts.len() > 4 || init.iter().any(|&part| part > 255) {
+        return None;
+    }
+    if *last >= 256u64.pow(5 - parts.len() as u32) {
+        return None;
+    }
+    let address = init.iter().enumerate().fold(*last, |address, (i, part)| {
+        address + (part << (8 * (3 - i)))
+    });
+    Some(std::net::Ipv4Addr::from(address as u32).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn from_whatwg(url: &str, base: Option<&str>) -> String {
+        Uri::_from_whatwg(url, base).unwrap().to_string()
+    }
+
+    #[test]
+    fn forgiving_syntax() {
+        let cases = [
+            (
+                " \thttp://EXAMPLE.com:80/a/../b?c d#e f\n",
+                "http://example.com/b?c%20d#e%20f",
+            ),
+            ("ht\ntp://exa\tmple.com/", "http://example.com/"),
+            (
+                "http:\\\\example.com\\a\\b?c\\d",
+                "http://example.com/a/b?c%5Cd",
+            ),
+            ("https:example.com", "https://example.com/"),
+            ("http:/example.com/", "http://example.com/"),
+            ("http://example.com/%2e/a/%2E%2e/b", "http://example.com/b"),
+            ("http://ex%41mple.com/", "http://example.com/"),
+            ("http://bücher.example/", "http://xn--bcher-kva.example/"),
+            ("mailto:User@Example.com", "mailto:User@Example.com"),
+        ];
+        for (url, expected) in cases {
+            assert_eq!(from_whatwg(url, None), expected, "url: {url:?}");
+        }
+    }
+
+    #[test]
+    fn ipv4_forms() {
+        let cases = [
+            ("http://0x7f.1/", "http://127.0.0.1/"),
+            ("http://2130706433/", "http://127.0.0.1/"),
+            ("http://0177.0.0.1/", "http://127.0.0.1/"),
+            ("http://192.168.0.257/", ""),
+        ];
+        for (url, expected) in cases {
+            match Uri::_from_whatwg(url, None) {
+                Ok(uri) => assert_eq!(uri.to_string(), expected, "url: {url}"),
+                Err(_) => assert!(expected.is_empty(), "url: {url}"),
+            }
+        }
+    }
+
+    #[test]
+    fn file_urls() {
+        let cases = [
+            ("file:///C:/dir/file", "file:///C:/dir/file"),
+            ("file:c:\\foo\\bar", "file:///c:/foo/bar"),
+            ("file://localhost/etc/hosts", "file:///etc/hosts"),
+            ("file://server/share", "file://server/share"),
+        ];
+        for (url, expected) in cases {
+            assert_eq!(from_whatwg(url, None), expected, "url: {url}");
+        }
+    }
+
+    #[test]
+    fn relative_to_base() {
+        let base = Some("http://example.com/a/b");
+        let cases = [
+            ("/path?x", "http://example.com/path?x"),
+            ("c", "http://example.com/a/c"),
+            ("http:c", "http://example.com/a/c"),
+            ("\\\\other.com\\x", "http://other.com/x"),
+            ("https:other.com", "https://other.com/"),
+            ("#frag", "http://example.com/a/b#frag"),
+        ];
+        for (url, expected) in cases {
+            assert_eq!(from_whatwg(url, base), expected, "url: {url}");
+        }
+    }
+
+    #[test]
+    fn invalid_urls() {
+        for url in [
+            "/relative",
+            "http://exa mple.com/",
+            "http://",
+            "http://a<b/",
+        ] {
+            assert!(Uri::_from_whatwg(url, None).is_err(), "url: {url}");
+        }
+    }
+}
@@ -0,0 +1,40 @@
+use crate::class::uri::normalize::PRESERVING_NORMALIZATIONS;
+use crate::class::uri::Uri;
+use ext_php_rs::prelude::*;
+
+#[php_class(name = "Takaram\\Psr7\\Internal\\UriComparator")]
+pub struct UriComparator;
+
+#[php_impl]
+impl UriComparator {
+    pub fn is_equivalent(a: &Uri, b: &Uri, normalization_flags: Option<i64>) -> bool {
+        let flags = normalization_flags.unwrap_or(PRESERVING_NORMALIZATIONS);
+        a.normalize_with(flags).to_string() == b.normalize_with(flags).to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::class::uri::normalize::SORT_QUERY_PARAMETERS;
+
+    #[test]
+    fn is_equivalent() {
+        let a = Uri::new("http://example.com:80/a/./b?x=%7e").unwrap();
+        let b = Uri::new("http://example.com/a/b?x=~").unwrap();
+        assert!(UriComparator::is_equivalent(&a, &b, None));
+        assert!(!UriComparator::is_equivalent(&a, &b, Some(0)));
+    }
+
+    #[test]
+    fn is_equivalent_with_flags() {
+        let a = Uri::new("http://example.com/?b=2&a=1").unwrap();
+        let b = Uri::new("http://example.com/?a=1&b=2").unwrap();
+        assert!(!UriComparator::is_equivalent(&a, &b, None));
+        assert!(UriComparator::is_equivalent(
+            &a,
+            &b,
+            Some(PRESERVING_NORMALIZATIONS | SORT_QUERY_PARAMETERS)
+        ));
+    }
+}
@@ -0,0 +1,66 @@
+use crate::class::uri::Uri;
+use crate::util::invalid_argument_exception;
+use ext_php_rs::prelude::*;
+use ext_php_rs::types::Zval;
+
+// PSR-17 UriFactoryInterface.
+#[php_class(name = "Takaram\\Psr7\\Internal\\UriFactory")]
+pub struct UriFactory;
+
+#[php_impl]
+impl UriFactory {
+    pub fn __construct() -> Self {
+        Self
+    }
+
+    pub fn create_uri(&self, uri: Option<String>) -> PhpResult<Uri> {
+        Uri::new(uri.unwrap_or_default())
+            .map_err(|err| PhpException::new(err, 0, invalid_argument_exception()))
+    }
+}
+
+// The PSR-17 factories take a URI as a string or as a Uri.
+pub(crate) fn uri_argument(uri: &Zval) -> PhpResult<Uri> {
+    uri_or_string(uri.extract::<&Uri>(), uri.str())
+        .map_err(|err| PhpException::new(err, 0, invalid_argument_exception()))
+}
+
+pub(crate) fn uri_or_string(uri: Option<&Uri>, string: Option<&str>) -> Result<Uri, String> {
+    match (uri, string) {
+        (Some(uri), _) => Ok(uri.clone()),
+        (None, Some(string)) => Uri::new(string),
+        (None, None) => Err("URI must be a string or a Uri".into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn create_uri() {
+        let factory = UriFactory::__construct();
+        let uri = factory
+            .create_uri(Some("https://example.com/a?b#c".into()))
+            .unwrap();
+        assert_eq!(uri.to_string(), "https://example.com/a?b#c");
+        assert_eq!(factory.create_uri(None).unwrap().to_string(), "");
+    }
+
+    #[test]
+    fn uri_or_string() {
+        let uri = Uri::new("http://example.com/a").unwrap();
+        assert_eq!(
+            super::uri_or_string(Some(&uri), None).unwrap().to_string(),
+            "http://example.com/a"
+        );
+        assert_eq!(
+            super::uri_or_string(None, Some("/b?c"))
+                .unwrap()
+                .to_string(),
+            "/b?c"
+        );
+        assert!(super::uri_or_string(None, None).is_err());
+        assert!(super::uri_or_string(None, Some("http://[::1/")).is_err());
+    }
+}
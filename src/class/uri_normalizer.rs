@@ -0,0 +1,23 @@
+use crate::class::uri::normalize;
+use crate::class::uri::Uri;
+use ext_php_rs::prelude::*;
+
+#[php_class(name = "Takaram\\Psr7\\Internal\\UriNormalizer")]
+pub struct UriNormalizer;
+
+#[php_impl]
+impl UriNormalizer {
+    const CAPITALIZE_PERCENT_ENCODING: i64 = normalize::CAPITALIZE_PERCENT_ENCODING;
+    const DECODE_UNRESERVED_CHARACTERS: i64 = normalize::DECODE_UNRESERVED_CHARACTERS;
+    const CONVERT_EMPTY_PATH: i64 = normalize::CONVERT_EMPTY_PATH;
+    const REMOVE_DEFAULT_HOST: i64 = normalize::REMOVE_DEFAULT_HOST;
+    const REMOVE_DEFAULT_PORT: i64 = normalize::REMOVE_DEFAULT_PORT;
+    const REMOVE_DOT_SEGMENTS: i64 = normalize::REMOVE_DOT_SEGMENTS;
+    const REMOVE_DUPLICATE_SLASHES: i64 = normalize::REMOVE_DUPLICATE_SLASHES;
+    const SORT_QUERY_PARAMETERS: i64 = normalize::SORT_QUERY_PARAMETERS;
+    const PRESERVING_NORMALIZATIONS: i64 = normalize::PRESERVING_NORMALIZATIONS;
+
+    pub fn normalize(uri: &Uri, flags: Option<i64>) -> Uri {
+        uri.normalize_with(flags.unwrap_or(normalize::PRESERVING_NORMALIZATIONS))
+    }
+}
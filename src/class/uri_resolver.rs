@@ -0,0 +1,16 @@
+use crate::class::uri::Uri;
+use ext_php_rs::prelude::*;
+
+#[php_class(name = "Takaram\\Psr7\\Internal\\UriResolver")]
+pub struct UriResolver;
+
+#[php_impl]
+impl UriResolver {
+    pub fn resolve(base: &Uri, rel: &Uri) -> Uri {
+        base.resolve(rel)
+    }
+
+    pub fn relativize(base: &Uri, target: &Uri) -> Uri {
+        base.relativize(target)
+    }
+}
@@ -0,0 +1,398 @@
+use crate::class::uri::Uri;
+use crate::query::QueryValue;
+use crate::util::{
+    invalid_argument_exception, is_unreserved, percent_encode, push_percent_encoded,
+};
+use ext_php_rs::prelude::*;
+use ext_php_rs::types::{ZendHashTable, Zval};
+
+// Reserved characters (RFC 3986, section 2.2) that are not sub-delims.
+const GEN_DELIMS: &[u8] = b":/?#[]@";
+
+#[php_class(name = "Takaram\\Psr7\\Internal\\UriTemplate")]
+pub struct UriTemplate;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    String(String),
+    List(Vec<String>),
+    Assoc(Vec<(String, String)>),
+}
+
+impl Value {
+    // Nulls, objects and empty arrays are undefined (RFC 6570, section 2.3).
+    fn from_zval(zval: &Zval) -> Option<Self> {
+        let Some(array) = zval.array() else {
+            return scalar(zval).map(Self::String);
+        };
+        if array.is_empty() {
+            None
+        } else if array.has_sequential_keys() {
+            Some(Self::List(array.values().filter_map(scalar).collect()))
+        } else {
+            let entries = array
+                .iter()
+                .filter_map(|(key, value)| Some((key.to_string(), scalar(value)?)));
+            Some(Self::Assoc(entries.collect()))
+        }
+    }
+}
+
+fn scalar(zval: &Zval) -> Option<String> {
+    match QueryValue::from_zval(zval)? {
+        QueryValue::Scalar(value) => Some(value),
+        QueryValue::Array(_) => None,
+    }
+}
+
+struct Operator {
+    first: &'static str,
+    sep: &'static str,
+    named: bool,
+    if_empty: &'static str,
+    allow_reserved: bool,
+}
+
+// RFC 6570, appendix A
+fn operator(op: Option<char>) -> Result<Operator, String> {
+    let (first, sep, named, if_empty, allow_reserved) = match op {
+        None => ("", ",", false, "", false),
+        Some('+') => ("", ",", false, "", true),
+        Some('#') => ("#", ",", false, "", true),
+        Some('.') => (".", ".", false, "", false),
+        Some('/') => ("/", "/", false, "", false),
+        Some(';') => (";", ";", true, "", false),
+        Some('?') => ("?", "&", true, "=", false),
+        Some('&') => ("&", "&", true, "=", false),
+        Some(op) => return Err(format!("Unsupported URI template operator: {}", op)),
+    };
+    Ok(Operator {
+        first,
+        sep,
+        named,
+        if_empty,
+        allow_reserved,
+    })
+}
+
+struct VarSpec<'a> {
+    name: &'a str,
+    prefix: Option<usize>,
+    explode: bool,
+}
+
+fn parse_var_spec(spec: &str) -> Result<VarSpec<'_>, String> {
+    let (name, prefix, explode) = if let Some(name) = spec.strip_suffix('*') {
+        (name, None, true)
+    } else if let Some((name, length)) = spec.split_once(':') {
+        let prefix = Some(length)
+            .filter(|length| !length.starts_with('0'))
+            .and_then(|length| length.parse::<usize>().ok())
+            .filter(|length| (1..10000).contains(length))
+            .ok_or_else(|| format!("Invalid prefix length in URI template: {}", spec))?;
+        (name, Some(prefix), false)
+    } else {
+        (spec, None, false)
+    };
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && !name.ends_with('.')
+        && !name.contains("..")
+        && name
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || b"_.%".contains(&byte));
+    if !valid {
+        return Err(format!("Invalid variable name in URI template: {}", spec));
+    }
+    Ok(VarSpec {
+        name,
+        prefix,
+        explode,
+    })
+}
+
+/// Expands `template` as described in RFC 6570, up to level 4.
+fn expand(template: &str, variables: &[(String, Value)]) -> Result<String, String> {
+    let mut result = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        result.push_str(&percent_encode(&rest[..start], GEN_DELIMS));
+        let end = rest[start..]
+            .find('}')
+            .ok_or("Unclosed expression in URI template")?;
+        expand_expression(&mut result, &rest[start + 1..start + end], variables)?;
+        rest = &rest[start + end + 1..];
+    }
+    if rest.contains('}') {
+        return Err("Unopened expression in URI template".to_string());
+    }
+    result.push_str(&percent_encode(rest, GEN_DELIMS));
+    Ok(result)
+}
+
+fn expand_expression(
+    result: &mut String,
+    expression: &str,
+    variables: &[(String, Value)],
+) -> Result<(), String> {
+    let op = expression
+        .chars()
+        .next()
+        .filter(|c| !c.is_ascii_alphanumeric() && *c != '_' && *c != '%');
+    let operator = operator(op)?;
+    let specs = &expression[op.map_or(0, char::len_utf8)..];
+    let mut first = true;
+    for spec in specs.split(',') {
+        let spec = parse_var_spec(spec)?;
+        let Some((_, value)) = variables.iter().find(|(name, _)| name == spec.name) else {
+            continue;
+        };
+        result.push_str(if first { operator.first } else { operator.sep });
+        first = false;
+        expand_value(result, &operator, &spec, value);
+    }
+    Ok(())
+}
+
+fn expand_value(result: &mut String, operator: &Operator, spec: &VarSpec, value: &Value) {
+    let encode = |str: &str| encode(str, operator.allow_reserved);
+    let push_named = |result: &mut String, name: &str, value: &str| {
+        result.push_str(name);
+        if value.is_empty() {
+            result.push_str(operator.if_empty);
+        } else {
+            result.push('=');
+            result.push_str(value);
+        }
+    };
+    match value {
+        Value::String(value) => {
+            let value = match spec.prefix {
+                Some(length) => encode(&value.chars().take(length).collect::<String>()),
+                None => encode(value),
+            };
+            if operator.named {
+                push_named(result, spec.name, &value);
+            } else {
+                result.push_str(&value);
+            }
+        }
+        Value::List(items) if spec.explode => {
+            let items = items.iter().map(|item| {
+                let item = encode(item);
+                if operator.named {
+                    let mut pair = String::new();
+                    push_named(&mut pair, spec.name, &item);
+                    pair
+                } else {
+                    item
+                }
+            });
+            result.push_str(&items.collect::<Vec<_>>().join(operator.sep));
+        }
+        Value::Assoc(entries) if spec.explode => {
+            let entries = entries.iter().map(|(key, value)| {
+                let (key, value) = (encode(key), encode(value));
+                if operator.named {
+                    let mut pair = String::new();
+                    push_named(&mut pair, &key, &value);
+                    pair
+                } else {
+                    format!("{}={}", key, value)
+                }
+            });
+            result.push_str(&entries.collect::<Vec<_>>().join(operator.sep));
+        }
+        Value::List(items) => {
+            let items: Vec<_> = items.iter().map(|item| encode(item)).collect();
+            push_composite(result, operator, spec, &items.join(","));
+        }
+        Value::Assoc(entries) => {
+            let entries: Vec<_> = entries
+                .iter()
+                .map(|(key, value)| format!("{},{}", encode(key), encode(value)))
+                .collect();
+            push_composite(result, operator, spec, &entries.join(","));
+        }
+    }
+}
+
+fn push_composite(result: &mut String, operator: &Operator, spec: &VarSpec, value: &str) {
+    if operator.named {
+        result.push_str(spec.name);
+        result.push('=');
+    }
+    result.push_str(value);
+}
+
+// Reserved expansion keeps reserved characters and existing
+// percent-encoded sequences; otherwise only unreserved characters remain.
+fn encode(str: &str, allow_reserved: bool) -> String {
+    if allow_reserved {
+        return percent_encode(str, GEN_DELIMS);
+    }
+    let mut result = String::with_capacity(str.len());
+    for byte in str.bytes() {
+        if is_unreserved(byte) {
+            result.push(byte as char);
+        } else {
+            push_percent_encoded(&mut result, byte);
+        }
+    }
+    result
+}
+
+#[php_impl]
+impl UriTemplate {
+    pub fn expand(template: &str, variables: &ZendHashTable) -> PhpResult<Uri> {
+        let variables: Vec<_> = variables
+            .iter()
+            .filter_map(|(name, value)| Some((name.to_string(), Value::from_zval(value)?)))
+            .collect();
+        expand(template, &variables)
+            .and_then(Uri::new)
+            .map_err(|err| PhpException::new(err, 0, invalid_argument_exception()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn list(items: &[&str]) -> Value {
+        Value::List(items.iter().map(|item| item.to_string()).collect())
+    }
+
+    // The example variables of RFC 6570, section 3.2
+    fn variables() -> Vec<(String, Value)> {
+        let string = |value: &str| Value::String(value.to_string());
+        let keys = Value::Assoc(vec![
+            ("semi".to_string(), ";".to_string()),
+            ("dot".to_string(), ".".to_string()),
+            ("comma".to_string(), ",".to_string()),
+        ]);
+        [
+            ("count", list(&["one", "two", "three"])),
+            ("dom", list(&["example", "com"])),
+            ("dub", string("me/too")),
+            ("hello", string("Hello World!")),
+            ("half", string("50%")),
+            ("var", string("value")),
+            ("who", string("fred")),
+            ("base", string("http://example.com/home/")),
+            ("path", string("/foo/bar")),
+            ("list", list(&["red", "green", "blue"])),
+            ("keys", keys),
+            ("v", string("6")),
+            ("x", string("1024")),
+            ("y", string("768")),
+            ("empty", string("")),
+        ]
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value))
+        .collect()
+    }
+
+    fn assert_expansions(cases: &[(&str, &str)]) {
+        let variables = variables();
+        for (template, expected) in cases {
+            assert_eq!(
+                expand(template, &variables).unwrap(),
+                *expected,
+                "template: {template}"
+            );
+        }
+    }
+
+    #[test]
+    fn simple_and_reserved_expansion() {
+        assert_expansions(&[
+            ("{var}", "value"),
+            ("{hello}", "Hello%20World%21"),
+            ("{half}", "50%25"),
+            ("O{empty}X", "OX"),
+            ("O{undef}X", "OX"),
+            ("{x,y}", "1024,768"),
+            ("{x,hello,y}", "1024,Hello%20World%21,768"),
+            ("{+var}", "value"),
+            ("{+hello}", "Hello%20World!"),
+            ("{+half}", "50%25"),
+            ("{base}index", "http%3A%2F%2Fexample.com%2Fhome%2Findex"),
+            ("{+base}index", "http://example.com/home/index"),
+            ("{+path,x}/here", "/foo/bar,1024/here"),
+            ("{#x,hello,y}", "#1024,Hello%20World!,768"),
+            ("{#path:6}/here", "#/foo/b/here"),
+        ]);
+    }
+
+    #[test]
+    fn label_path_and_parameter_expansion() {
+        assert_expansions(&[
+            ("X{.var}", "X.value"),
+            ("X{.x,y}", "X.1024.768"),
+            ("{.dom*}", ".example.com"),
+            ("{/var,x}/here", "/value/1024/here"),
+            ("{/var,empty}", "/value/"),
+            ("{/var,undef}", "/value"),
+            ("{/var:1,var}", "/v/value"),
+            ("{/list*,path:4}", "/red/green/blue/%2Ffoo"),
+            ("{;x,y,empty}", ";x=1024;y=768;empty"),
+            ("{;hello:5}", ";hello=Hello"),
+            ("{;list*}", ";list=red;list=green;list=blue"),
+        ]);
+    }
+
+    #[test]
+    fn query_expansion() {
+        assert_expansions(&[
+            ("{?x,y,empty}", "?x=1024&y=768&empty="),
+            ("{?x,y,undef}", "?x=1024&y=768"),
+            ("{?var:3}", "?var=val"),
+            ("{?list}", "?list=red,green,blue"),
+            ("{?list*}", "?list=red&list=green&list=blue"),
+            ("{?keys}", "?keys=semi,%3B,dot,.,comma,%2C"),
+            ("{?keys*}", "?semi=%3B&dot=.&comma=%2C"),
+            ("?fixed=yes{&x}", "?fixed=yes&x=1024"),
+            ("{&keys*}", "&semi=%3B&dot=.&comma=%2C"),
+        ]);
+    }
+
+    #[test]
+    fn composite_values() {
+        assert_expansions(&[
+            ("{list}", "red,green,blue"),
+            ("{list*}", "red,green,blue"),
+            ("{keys}", "semi,%3B,dot,.,comma,%2C"),
+            ("{keys*}", "semi=%3B,dot=.,comma=%2C"),
+            ("{+keys}", "semi,;,dot,.,comma,,"),
+            ("{+keys*}", "semi=;,dot=.,comma=,"),
+            ("X{.keys*}", "X.semi=%3B.dot=..comma=%2C"),
+            ("{/keys*}", "/semi=%3B/dot=./comma=%2C"),
+            ("{;keys*}", ";semi=%3B;dot=.;comma=%2C"),
+        ]);
+    }
+
+    #[test]
+    fn literals_are_encoded() {
+        assert_expansions(&[("/a b/{var}?q=%41", "/a%20b/value?q=%41")]);
+    }
+
+    #[test]
+    fn invalid_templates() {
+        let variables = variables();
+        for template in [
+            "{var",
+            "var}",
+            "{=var}",
+            "{var:0}",
+            "{var:10000}",
+            "{}",
+            "{a b}",
+        ] {
+            assert!(
+                expand(template, &variables).is_err(),
+                "template: {template}"
+            );
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use crate::query::{
+    build_query_pairs, int_key, parse_query, QueryEncoding, QueryValue, PHP_QUERY_RFC1738,
+    PHP_QUERY_RFC3986,
+};
+use crate::util::invalid_argument_exception;
+use ext_php_rs::boxed::ZBox;
+use ext_php_rs::prelude::*;
+use ext_php_rs::types::{ZendHashTable, Zval};
+
+// Zero disables encoding or decoding, like passing `false` to guzzle.
+fn encoding(value: i64) -> PhpResult<Option<QueryEncoding>> {
+    if value == 0 {
+        return Ok(None);
+    }
+    QueryEncoding::try_from(value)
+        .map(Some)
+        .map_err(|err| PhpException::new(err.into(), 0, invalid_argument_exception()))
+}
+
+#[php_function(name = "Takaram\\Psr7\\parse_query")]
+pub fn parse_query_function(
+    str: &str,
+    url_encoding: Option<i64>,
+) -> PhpResult<ZBox<ZendHashTable>> {
+    let decoding = encoding(url_encoding.unwrap_or(PHP_QUERY_RFC1738))?;
+    let mut result = ZendHashTable::new();
+    for (key, mut values) in parse_query(str, decoding) {
+        let mut value = Zval::new();
+        if values.len() == 1 {
+            match values.remove(0) {
+                Some(str) => value.set_string(&str, false)?,
+                None => value.set_null(),
+            }
+        } else {
+            let mut list = ZendHashTable::new();
+            for item in values {
+                list.push(item)?;
+            }
+            value.set_hashtable(list);
+        }
+        match int_key(&key) {
+            Some(index) => result.insert_at_index(index, value)?,
+            None => result.insert(&key, value)?,
+        }
+    }
+    Ok(result)
+}
+
+#[php_function(name = "Takaram\\Psr7\\build_query")]
+pub fn build_query_function(params: &ZendHashTable, encoding: Option<i64>) -> PhpResult<String> {
+    let encoding = self::encoding(encoding.unwrap_or(PHP_QUERY_RFC3986))?;
+    let params: Vec<_> = params
+        .iter()
+        .map(|(key, value)| (key.to_string(), values(value)))
+        .collect();
+    Ok(build_query_pairs(&params, encoding))
+}
+
+fn values(zval: &Zval) -> Vec<Option<String>> {
+    match zval.array() {
+        Some(array) => array.values().filter_map(scalar).collect(),
+        None => scalar(zval).into_iter().collect(),
+    }
+}
+
+// A null value is kept as a bare key; nested arrays and objects are dropped.
+fn scalar(zval: &Zval) -> Option<Option<String>> {
+    if zval.is_null() {
+        Some(None)
+    } else if zval.is_array() {
+        None
+    } else {
+        QueryValue::from_zval(zval).and_then(|value| match value {
+            QueryValue::Scalar(str) => Some(Some(str)),
+            QueryValue::Array(_) => None,
+        })
+    }
+}
@@ -0,0 +1,384 @@
+use crate::util::is_token;
+
+// Header fields in insertion order. Names keep the case they were given
+// but are matched case-insensitively.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct HeaderMap {
+    entries: Vec<(String, Vec<String>)>,
+}
+
+impl HeaderMap {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.entries
+            .iter()
+            .position(|(key, _)| key.eq_ignore_ascii_case(name))
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub(crate) fn contains(&self, name: &str) -> bool {
+        self.position(name).is_some()
+    }
+
+    pub(crate) fn get(&self, name: &str) -> &[String] {
+        self.position(name)
+            .map_or(&[], |pos| self.entries[pos].1.as_slice())
+    }
+
+    pub(crate) fn line(&self, name: &str) -> String {
+        self.get(name).join(", ")
+    }
+
+    pub(crate) fn set(&mut self, name: &str, values: Vec<String>) -> Result<(), &'static str> {
+        let values = validate(name, values)?;
+        self.replace(name.to_string(), values);
+        Ok(())
+    }
+
+    // Sets several headers at once. Every entry is validated before any is
+    // applied, so an invalid one leaves the map unchanged.
+    pub(crate) fn set_all(
+        &mut self,
+        entries: Vec<(String, Vec<String>)>,
+    ) -> Result<(), &'static str> {
+        let entries = entries
+            .into_iter()
+            .map(|(name, values)| Ok((validate(&name, values)?, name)))
+            .collect::<Result<Vec<_>, &'static str>>()?;
+        for (values, name) in entries {
+            self.replace(name, values);
+        }
+        Ok(())
+    }
+
+    fn replace(&mut self, name: String, values: Vec<String>) {
+        match self.position(&name) {
+            Some(pos) => self.entries[pos] = (name, values),
+            None => self.entries.push((name, values)),
+        }
+    }
+
+    // `Host` is conventionally sent first, so it is moved to the front.
+    pub(crate) fn set_first(
+        &mut self,
+        name: &str,
+        values: Vec<String>,
+    ) -> Result<(), &'static str> {
+        let values = validate(name, values)?;
+        self.remove(name);
+        self.entries.insert(0, (name.to_string(), values));
+        Ok(())
+    }
+
+    pub(crate) fn append(&mut self, name: &str, values: Vec<String>) -> Result<(), &'static str> {
+        let values = validate(name, values)?;
+        match self.position(name) {
+            Some(pos) => self.entries[pos].1.extend(values),
+            None => self.entries.push((name.to_string(), values)),
+        }
+        Ok(())
+    }
+
+    pub(crate) fn remove(&mut self, name: &str) {
+        self.entries
+            .retain(|(key, _)| !key.eq_ignore_ascii_case(name));
+    }
+
+    pub(crate) fn remove_prefix(&mut self, prefix: &str) {
+        self.entries.retain(|(key, _)| {
+            !key.get(..prefix.len())
+                .is_some_and(|head| head.eq_ignore_ascii_case(prefix))
+        });
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&str, &[String])> {
+        self.entries
+            .iter()
+            .map(|(name, values)| (name.as_str(), values.as_slice()))
+    }
+}
+
+// Splits a structured header like `Accept` or `Cache-Control` into its
+// comma-separated elements, each a list of `;`-separated parts keyed by
+// what comes before an `=`, as guzzle's `Header::parse()` does.
+pub(crate) fn words(lines: &[String]) -> Vec<Vec<(Option<String>, String)>> {
+    lines
+        .iter()
+        .flat_map(|line| split_unquoted(line, ','))
+        .map(parameters)
+        .filter(|parts| !parts.is_empty())
+        .collect()
+}
+
+// The `;`-separated parts of one element, with quoted strings unescaped.
+pub(crate) fn parameters(element: &str) -> Vec<(Option<String>, String)> {
+    split_unquoted(element, ';')
+        .into_iter()
+        .filter_map(|part| match split_unquoted(part, '=').as_slice() {
+            [value] => Some((None, unquote(value.trim()))),
+            [key, ..] => Some((
+                Some(key.trim().to_string()),
+                unquote(part[key.len() + 1..].trim()),
+            )),
+            [] => None,
+        })
+        .filter(|(key, value)| {
+            key.as_ref()
+                .map_or(!value.is_empty(), |key| !key.is_empty())
+        })
+        .collect()
+}
+
+fn split_unquoted(str: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let (mut start, mut quoted, mut escaped) = (0, false, false);
+    for (pos, c) in str.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            c if c == separator && !quoted => {
+                parts.push(&str[start..pos]);
+                start = pos + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&str[start..]);
+    parts
+}
+
+fn unquote(value: &str) -> String {
+    let Some(inner) = value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+    else {
+        return value.to_string();
+    };
+    let mut unquoted = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unquoted.extend(chars.next()),
+            c => unquoted.push(c),
+        }
+    }
+    unquoted
+}
+
+// Rejects names that are not tokens and values that could split the
+// message (RFC 7230, section 3.2), and strips optional whitespace around
+// values, which is not part of them.
+fn validate(name: &str, values: Vec<String>) -> Result<Vec<String>, &'static str> {
+    if !is_token(name) {
+        return Err("Header name must be an RFC 7230 compatible string");
+    }
+    if values
+        .iter()
+        .any(|value| value.contains(['\r', '\n', '\0']))
+    {
+        return Err("Header values must not contain CR, LF or NUL characters");
+    }
+    Ok(values
+        .into_iter()
+        .map(|value| value.trim_matches([' ', '\t']).to_string())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn get_case_insensitive() {
+        let mut headers = HeaderMap::new();
+        headers
+            .set("Content-Type", values(&["text/plain"]))
+            .unwrap();
+        assert!(headers.contains("content-type"));
+        assert_eq!(headers.get("CONTENT-TYPE"), ["text/plain"]);
+        assert!(headers.get("Accept").is_empty());
+    }
+
+    #[test]
+    fn set_replaces_values_and_name() {
+        let mut headers = HeaderMap::new();
+        headers.set("x-foo", values(&["a", "b"])).unwrap();
+        headers.set("X-Foo", values(&["c"])).unwrap();
+        let entries: Vec<_> = headers.iter().collect();
+        assert_eq!(entries, [("X-Foo", &values(&["c"])[..])]);
+    }
+
+    #[test]
+    fn set_all() {
+        let mut headers = HeaderMap::new();
+        headers.set("Accept", values(&["*/*"])).unwrap();
+        headers
+            .set_all(vec![
+                ("accept".into(), values(&["text/html"])),
+                ("X-Foo".into(), values(&["a", "b"])),
+            ])
+            .unwrap();
+        let entries: Vec<_> = headers.iter().collect();
+        assert_eq!(
+            entries,
+            [
+                ("accept", &values(&["text/html"])[..]),
+                ("X-Foo", &values(&["a", "b"])[..]),
+            ]
+        );
+    }
+
+    #[test]
+    fn set_all_invalid_entry() {
+        let mut headers = HeaderMap::new();
+        headers.set("Accept", values(&["*/*"])).unwrap();
+        let result = headers.set_all(vec![
+            ("Accept".into(), values(&["text/html"])),
+            ("X-Bad".into(), values(&["a\r\nb"])),
+            ("X-Foo".into(), values(&["c"])),
+        ]);
+        assert!(result.is_err());
+        assert_eq!(headers.line("Accept"), "*/*");
+        assert!(!headers.contains("X-Foo"));
+    }
+
+    #[test]
+    fn append_keeps_existing_values() {
+        let mut headers = HeaderMap::new();
+        headers.set("Accept", values(&["text/html"])).unwrap();
+        headers
+            .append("accept", values(&["application/json"]))
+            .unwrap();
+        assert_eq!(headers.line("Accept"), "text/html, application/json");
+    }
+
+    #[test]
+    fn set_first_moves_to_front() {
+        let mut headers = HeaderMap::new();
+        headers.set("Accept", values(&["*/*"])).unwrap();
+        headers.set("Host", values(&["a.example"])).unwrap();
+        headers.set_first("host", values(&["b.example"])).unwrap();
+        let names: Vec<_> = headers.iter().map(|(name, _)| name).collect();
+        assert_eq!(names, ["host", "Accept"]);
+    }
+
+    #[test]
+    fn multiple_values() {
+        let mut headers = HeaderMap::new();
+        headers.append("Set-Cookie", values(&["a=1"])).unwrap();
+        headers
+            .append("set-cookie", values(&["b=2", "c=3"]))
+            .unwrap();
+        assert_eq!(headers.get("Set-Cookie"), ["a=1", "b=2", "c=3"]);
+        let names: Vec<_> = headers.iter().map(|(name, _)| name).collect();
+        assert_eq!(names, ["Set-Cookie"]);
+    }
+
+    #[test]
+    fn values_are_trimmed() {
+        let mut headers = HeaderMap::new();
+        headers.set("X-Foo", values(&[" a\t", "b "])).unwrap();
+        headers.append("X-Foo", values(&["\tc"])).unwrap();
+        assert_eq!(headers.get("X-Foo"), ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn invalid_name() {
+        let mut headers = HeaderMap::new();
+        assert!(headers.set("X Foo", values(&["a"])).is_err());
+        assert!(headers.append("X-Foo:", values(&["a"])).is_err());
+        assert!(headers.set("", values(&["a"])).is_err());
+        assert!(!headers.contains("X Foo"));
+    }
+
+    #[test]
+    fn invalid_value() {
+        let mut headers = HeaderMap::new();
+        for value in ["a\r\nSet-Cookie: x", "a\nb", "a\rb", "a\0b"] {
+            assert!(headers.set("X-Foo", values(&[value])).is_err());
+            assert!(headers.append("X-Foo", values(&["ok", value])).is_err());
+        }
+        assert!(!headers.contains("X-Foo"));
+    }
+
+    #[test]
+    fn remove() {
+        let mut headers = HeaderMap::new();
+        headers.set("Accept", values(&["*/*"])).unwrap();
+        headers.remove("ACCEPT");
+        assert!(!headers.contains("Accept"));
+        assert_eq!(headers.line("Accept"), "");
+    }
+
+    #[test]
+    fn remove_prefix() {
+        let mut headers = HeaderMap::new();
+        for name in [
+            "X-Forwarded-For",
+            "x-forwarded-proto",
+            "X-Forwarded",
+            "X-Request-Id",
+            "Host",
+        ] {
+            headers.set(name, values(&["1"])).unwrap();
+        }
+        headers.remove_prefix("X-FORWARDED-");
+        let names: Vec<_> = headers.iter().map(|(name, _)| name).collect();
+        assert_eq!(names, ["X-Forwarded", "X-Request-Id", "Host"]);
+    }
+
+    fn word(parts: &[(Option<&str>, &str)]) -> Vec<(Option<String>, String)> {
+        parts
+            .iter()
+            .map(|(key, value)| (key.map(str::to_string), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn words_accept() {
+        let lines = values(&["text/html;q=0.8, application/json", "*/*; q=0.1"]);
+        assert_eq!(
+            words(&lines),
+            [
+                word(&[(None, "text/html"), (Some("q"), "0.8")]),
+                word(&[(None, "application/json")]),
+                word(&[(None, "*/*"), (Some("q"), "0.1")]),
+            ]
+        );
+    }
+
+    #[test]
+    fn words_quoted_values() {
+        let lines = values(&[r#"for="[2001:db8::1]:80";proto=https, for="a,\"b\";c""#]);
+        assert_eq!(
+            words(&lines),
+            [
+                word(&[(Some("for"), "[2001:db8::1]:80"), (Some("proto"), "https")]),
+                word(&[(Some("for"), r#"a,"b";c"#)]),
+            ]
+        );
+        let lines = values(&["no-cache, , max-age=60;"]);
+        assert_eq!(
+            words(&lines),
+            [
+                word(&[(None, "no-cache")]),
+                word(&[(Some("max-age"), "60")]),
+            ]
+        );
+    }
+}
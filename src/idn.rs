@@ -0,0 +1,220 @@
+// Punycode (RFC 3492) parameters
+const BASE: u32 = 36;
+const T_MIN: u32 = 1;
+const T_MAX: u32 = 26;
+const SKEW: u32 = 38;
+const DAMP: u32 = 700;
+const INITIAL_BIAS: u32 = 72;
+const INITIAL_N: u32 = 128;
+
+// Ideographic and fullwidth full stops separate labels too (UTS #46).
+const LABEL_SEPARATORS: [char; 4] = ['.', '\u{3002}', '\u{ff0e}', '\u{ff61}'];
+
+/// Converts each non-ASCII label of `host` to its lowercased punycode
+/// A-label (`xn--...`), leaving ASCII labels untouched. This is only the
+/// lowercasing and label separator part of UTS #46: there is no NFC
+/// normalization, no mapping table (fullwidth letters stay as they are, `ß`
+/// is kept) and no validity check of the resulting labels.
+pub(crate) fn to_ascii(host: &str) -> Option<String> {
+    if host.is_ascii() {
+        return Some(host.to_string());
+    }
+    let labels = host
+        .split(LABEL_SEPARATORS)
+        .map(|label| {
+            if label.is_ascii() {
+                Some(label.to_string())
+            } else {
+                punycode_encode(&label.to_lowercase()).map(|encoded| format!("xn--{}", encoded))
+            }
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some(labels.join("."))
+}
+
+/// Converts each A-label of `host` back to Unicode. Labels that are not
+/// valid punycode are left as they are.
+pub(crate) fn to_unicode(host: &str) -> String {
+    host.split('.')
+        .map(|label| {
+            label
+                .get(..4)
+                .filter(|prefix| prefix.eq_ignore_ascii_case("xn--"))
+                .and_then(|_| punycode_decode(&label[4..]))
+                .unwrap_or_else(|| label.to_string())
+        })
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+fn punycode_encode(input: &str) -> Option<String> {
+    let input: Vec<u32> = input.chars().map(u32::from).collect();
+    let mut output: String = input
+        .iter()
+        .filter(|&&c| c < 0x80)
+        .filter_map(|&c| char::from_u32(c))
+        .collect();
+    let basic_len = output.len() as u32;
+    if basic_len > 0 {
+        output.push('-');
+    }
+
+    let mut n = INITIAL_N;
+    let mut delta: u32 = 0;
+    let mut bias = INITIAL_BIAS;
+    let mut handled = basic_len;
+    while (handled as usize) < input.len() {
+        let m = input.iter().copied().filter(|&c| c >= n).min()?;
+        delta = delta.checked_add((m - n).checked_mul(handled + 1)?)?;
+        n = m;
+        for &c in &input {
+            if c < n {
+                delta = delta.checked_add(1)?;
+            }
+            if c == n {
+                let mut q = delta;
+                let mut k = BASE;
+                loop {
+                    let t = threshold(k, bias);
+                    if q < t {
+                        break;
+                    }
+                    output.push(encode_digit(t + (q - t) % (BASE - t)));
+                    q = (q - t) / (BASE - t);
+                    k += BASE;
+                }
+                output.push(encode_digit(q));
+                bias = adapt(delta, handled + 1, handled == basic_len);
+                delta = 0;
+                handled += 1;
+            }
+        }
+        delta = delta.checked_add(1)?;
+        n += 1;
+    }
+    Some(output)
+}
+
+fn punycode_decode(input: &str) -> Option<String> {
+    let (basic, extended) = match input.rfind('-') {
+        Some(pos) => (&input[..pos], &input[pos + 1..]),
+        None => ("", input),
+    };
+    let mut output: Vec<char> = basic.chars().collect();
+    let mut n = INITIAL_N;
+    let mut i: u32 = 0;
+    let mut bias = INITIAL_BIAS;
+    let mut digits = extended.bytes().peekable();
+    while digits.peek().is_some() {
+        let old_i = i;
+        let mut w: u32 = 1;
+        let mut k = BASE;
+        loop {
+            let digit = decode_digit(digits.next()?)?;
+            i = i.checked_add(digit.checked_mul(w)?)?;
+            let t = threshold(k, bias);
+            if digit < t {
+                break;
+            }
+            w = w.checked_mul(BASE - t)?;
+            k += BASE;
+        }
+        let len = output.len() as u32 + 1;
+        bias = adapt(i - old_i, len, old_i == 0);
+        n = n.checked_add(i / len)?;
+        i %= len;
+        output.insert(i as usize, char::from_u32(n)?);
+        i += 1;
+    }
+    if output.is_empty() {
+        return None;
+    }
+    Some(output.into_iter().collect())
+}
+
+fn threshold(k: u32, bias: u32) -> u32 {
+    if k <= bias {
+        T_MIN
+    } else if k >= bias + T_MAX {
+        T_MAX
+    } else {
+        k - bias
+    }
+}
+
+fn adapt(delta: u32, num_points: u32, first_time: bool) -> u32 {
+    let mut delta = if first_time { delta / DAMP } else { delta / 2 };
+    delta += delta / num_points;
+    let mut k = 0;
+    while delta > ((BASE - T_MIN) * T_MAX) / 2 {
+        delta /= BASE - T_MIN;
+        k += BASE;
+    }
+    k + (BASE - T_MIN + 1) * delta / (delta + SKEW)
+}
+
+fn encode_digit(digit: u32) -> char {
+    let byte = if digit < 26 {
+        b'a' + digit as u8
+    } else {
+        b'0' + (digit - 26) as u8
+    };
+    byte as char
+}
+
+fn decode_digit(byte: u8) -> Option<u32> {
+    match byte {
+        b'a'..=b'z' => Some(u32::from(byte - b'a')),
+        b'A'..=b'Z' => Some(u32::from(byte - b'A')),
+        b'0'..=b'9' => Some(u32::from(byte - b'0') + 26),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn to_ascii_non_ascii_labels() {
+        assert_eq!(to_ascii("例え.jp").unwrap(), "xn--r8jz45g.jp");
+        assert_eq!(to_ascii("bücher.example").unwrap(), "xn--bcher-kva.example");
+        assert_eq!(to_ascii("例え.テスト").unwrap(), "xn--r8jz45g.xn--zckzah");
+    }
+
+    #[test]
+    fn to_ascii_lowercases_non_ascii_labels() {
+        assert_eq!(to_ascii("BÜCHER.example").unwrap(), "xn--bcher-kva.example");
+    }
+
+    // Pins the documented gap: no UTS #46 mapping or normalization.
+    #[test]
+    fn to_ascii_without_mapping() {
+        assert_eq!(to_ascii("straße.de").unwrap(), "xn--strae-oqa.de");
+        assert_ne!(to_ascii("ｅｘａｍｐｌｅ.com").unwrap(), "example.com");
+        assert_ne!(to_ascii("cafe\u{301}.fr"), to_ascii("café.fr"));
+    }
+
+    #[test]
+    fn to_ascii_ascii_host() {
+        assert_eq!(to_ascii("Example.com").unwrap(), "Example.com");
+    }
+
+    #[test]
+    fn to_ascii_label_separators() {
+        assert_eq!(to_ascii("例え。jp").unwrap(), "xn--r8jz45g.jp");
+    }
+
+    #[test]
+    fn to_unicode_a_labels() {
+        assert_eq!(to_unicode("xn--bcher-kva.example"), "bücher.example");
+        assert_eq!(to_unicode("XN--R8JZ45G.jp"), "例え.jp");
+        assert_eq!(to_unicode("xn--r8jz45g.xn--zckzah"), "例え.テスト");
+    }
+
+    #[test]
+    fn to_unicode_keeps_invalid_labels() {
+        assert_eq!(to_unicode("xn--!.example.com"), "xn--!.example.com");
+        assert_eq!(to_unicode("xn--.com"), "xn--.com");
+    }
+}
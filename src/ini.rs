@@ -0,0 +1,66 @@
+use crate::class::uri::limits::{Limits, DEFAULT_MAX_LENGTH};
+use crate::class::uri::ParseMode;
+use ext_php_rs::flags::IniEntryPermission;
+use ext_php_rs::zend::{ExecutorGlobals, IniEntryDef};
+
+const URI_PARSE_MODE: &str = "psr7.uri_parse_mode";
+const URI_MAX_LENGTH: &str = "psr7.uri_max_length";
+const URI_MAX_COMPONENT_LENGTH: &str = "psr7.uri_max_component_length";
+
+/// Registers the extension's ini settings and applies them. They are read
+/// once at startup, so they can only be set in php.ini.
+pub(crate) fn startup(module_number: i32) {
+    let entries = [
+        (URI_PARSE_MODE, String::new()),
+        (URI_MAX_LENGTH, DEFAULT_MAX_LENGTH.to_string()),
+        (URI_MAX_COMPONENT_LENGTH, "0".to_string()),
+    ]
+    .into_iter()
+    .map(|(name, default)| IniEntryDef::new(name.into(), default, IniEntryPermission::System))
+    .collect();
+    IniEntryDef::register(entries, module_number);
+
+    let values = ExecutorGlobals::get().ini_values();
+    let value = |name: &str| values.get(name).cloned().flatten().unwrap_or_default();
+    parse_mode(&value(URI_PARSE_MODE)).set_default();
+    Limits {
+        length: size(&value(URI_MAX_LENGTH), DEFAULT_MAX_LENGTH),
+        component_length: size(&value(URI_MAX_COMPONENT_LENGTH), 0),
+    }
+    .set_default();
+}
+
+// `strict` or `lenient`; anything else keeps components as given.
+fn parse_mode(value: &str) -> ParseMode {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "strict" => ParseMode::Strict,
+        "lenient" => ParseMode::Lenient,
+        _ => ParseMode::Standard,
+    }
+}
+
+// A byte count, where `0` disables the limit.
+fn size(value: &str, default: usize) -> usize {
+    value.trim().parse().unwrap_or(default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_mode_values() {
+        assert_eq!(parse_mode("strict"), ParseMode::Strict);
+        assert_eq!(parse_mode(" Lenient"), ParseMode::Lenient);
+        assert_eq!(parse_mode(""), ParseMode::Standard);
+        assert_eq!(parse_mode("unknown"), ParseMode::Standard);
+    }
+
+    #[test]
+    fn size_values() {
+        assert_eq!(size("8192", 1), 8192);
+        assert_eq!(size(" 0 ", 1), 0);
+        assert_eq!(size("-1", 1), 1);
+        assert_eq!(size("8K", 1), 1);
+    }
+}
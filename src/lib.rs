@@ -1,11 +1,48 @@
 #![cfg_attr(windows, feature(abi_vectorcall))]
-use crate::class::uri::Uri;
+use crate::class::header_iterator::HeaderIterator;
+use crate::class::request::Request;
+use crate::class::request_factory::RequestFactory;
+use crate::class::response::Response;
+use crate::class::response_factory::ResponseFactory;
+use crate::class::server_request::ServerRequest;
+use crate::class::server_request_factory::ServerRequestFactory;
+use crate::class::stream::Stream;
+use crate::class::stream_exception::StreamException;
+use crate::class::stream_factory::StreamFactory;
+use crate::class::uploaded_file::UploadedFile;
+use crate::class::uploaded_file_factory::UploadedFileFactory;
+pub use crate::class::uri::Uri;
+use crate::class::uri_comparator::UriComparator;
+use crate::class::uri_factory::UriFactory;
+use crate::class::uri_normalizer::UriNormalizer;
+use crate::class::uri_resolver::UriResolver;
+use crate::class::uri_template::UriTemplate;
+use crate::function::{build_query_function, parse_query_function};
 use ext_php_rs::prelude::*;
+use std::os::raw::c_int;
 
 mod class;
+mod function;
+mod header_map;
+mod idn;
+mod ini;
+mod message;
+mod multipart;
+mod public_suffix;
+mod query;
 mod util;
 
+#[php_startup]
+pub fn startup(_ty: i32, module_number: i32) {
+    ini::startup(module_number);
+}
+
+extern "C" fn request_shutdown(_ty: c_int, _module_number: c_int) -> c_int {
+    class::uri::request_shutdown();
+    0
+}
+
 #[php_module]
 pub fn get_module(module: ModuleBuilder) -> ModuleBuilder {
-    module
+    module.request_shutdown_function(request_shutdown)
 }
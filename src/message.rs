@@ -0,0 +1,465 @@
+use crate::class::stream::Stream;
+use crate::header_map::{words, HeaderMap};
+use crate::util::invalid_argument_exception;
+use ext_php_rs::boxed::ZBox;
+use ext_php_rs::prelude::*;
+use ext_php_rs::types::{ZendHashTable, Zval};
+use std::io;
+
+// State shared by every message class. Each PHP class exposes the PSR-7
+// MessageInterface methods as thin wrappers around these.
+#[derive(Clone)]
+pub(crate) struct Message {
+    pub(crate) protocol_version: String,
+    pub(crate) headers: HeaderMap,
+    pub(crate) body: Stream,
+    // Fields sent after a chunked body.
+    pub(crate) trailers: HeaderMap,
+}
+
+const CHUNK_SIZE: usize = 8192;
+
+impl Message {
+    pub(crate) fn new(headers: HeaderMap, body: Option<Stream>, version: Option<String>) -> Self {
+        Self {
+            protocol_version: version.unwrap_or_else(|| "1.1".to_string()),
+            headers,
+            body: body.unwrap_or_else(|| Stream::from_bytes(Vec::new())),
+            trailers: HeaderMap::new(),
+        }
+    }
+
+    pub(crate) fn headers_table(&self) -> PhpResult<ZBox<ZendHashTable>> {
+        fields_table(&self.headers)
+    }
+
+    // The elements of a structured header, each an array of its parts where
+    // `key=value` parts are keyed and the others are listed.
+    pub(crate) fn header_words(&self, name: &str) -> PhpResult<ZBox<ZendHashTable>> {
+        let mut table = ZendHashTable::new();
+        for parts in words(self.headers.get(name)) {
+            let mut element = ZendHashTable::new();
+            for (key, value) in parts {
+                match key {
+                    Some(key) => element.insert(&key, value)?,
+                    None => element.push(value)?,
+                }
+            }
+            table.push(element)?;
+        }
+        Ok(table)
+    }
+
+    // Adds the protocol version, headers, body and any trailers, for
+    // `__serialize()` and `__debugInfo()`.
+    pub(crate) fn insert_fields(&self, data: &mut ZendHashTable) -> PhpResult<()> {
+        data.insert("protocolVersion", self.protocol_version.clone())?;
+        data.insert("headers", self.headers_table()?)?;
+        data.insert("body", self.body.clone())?;
+        if !self.trailers.is_empty() {
+            data.insert("trailers", fields_table(&self.trailers)?)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn from_serialized(data: &ZendHashTable) -> PhpResult<Self> {
+        let headers = match data.get("headers").and_then(Zval::array) {
+            Some(headers) => headers_from_array(headers)?,
+            None => HeaderMap::new(),
+        };
+        let body = data
+            .get("body")
+            .and_then(|body| body.extract::<&Stream>())
+            .cloned();
+        let version = data.get("protocolVersion").and_then(Zval::string);
+        let trailers = match data.get("trailers").and_then(Zval::array) {
+            Some(trailers) => headers_from_array(trailers)?,
+            None => HeaderMap::new(),
+        };
+        Ok(Self {
+            trailers,
+            ..Self::new(headers, body, version)
+        })
+    }
+
+    // The message as sent on the wire, after `start_line`. Like guzzle's
+    // `Message::toString()`, values are joined on one line except for
+    // Set-Cookie, which cannot be folded. A body whose final transfer
+    // coding is chunked is framed in chunks, followed by the trailers.
+    pub(crate) fn to_raw(&self, start_line: &str) -> io::Result<Vec<u8>> {
+        let mut raw = format!("{start_line}\r\n").into_bytes();
+        push_fields(&mut raw, &self.headers);
+        raw.extend_from_slice(b"\r\n");
+        let body = self.body.contents(0)?;
+        if !self.is_chunked() {
+            raw.extend(body);
+            return Ok(raw);
+        }
+        for chunk in body.chunks(CHUNK_SIZE) {
+            raw.extend(format!("{:x}\r\n", chunk.len()).into_bytes());
+            raw.extend_from_slice(chunk);
+            raw.extend_from_slice(b"\r\n");
+        }
+        raw.extend_from_slice(b"0\r\n");
+        push_fields(&mut raw, &self.trailers);
+        raw.extend_from_slice(b"\r\n");
+        Ok(raw)
+    }
+
+    fn is_chunked(&self) -> bool {
+        self.headers
+            .line("Transfer-Encoding")
+            .rsplit(',')
+            .next()
+            .is_some_and(|coding| coding.trim().eq_ignore_ascii_case("chunked"))
+    }
+
+    // Sets `Transfer-Encoding: chunked`, which replaces Content-Length.
+    pub(crate) fn with_chunked_body(&self) -> Result<Self, &'static str> {
+        self.with_header_changes(|headers| {
+            headers.remove("Content-Length");
+            headers.set("Transfer-Encoding", vec!["chunked".to_string()])
+        })
+    }
+
+    pub(crate) fn with_trailer(
+        &self,
+        name: &str,
+        values: Vec<String>,
+    ) -> Result<Self, &'static str> {
+        let mut message = self.clone();
+        message.trailers.set(name, values)?;
+        Ok(message)
+    }
+
+    pub(crate) fn with_protocol_version(&self, version: &str) -> Self {
+        Self {
+            protocol_version: version.to_string(),
+            ..self.clone()
+        }
+    }
+
+    // Applies every change to a single copy of the headers, so building a
+    // message with many of them does not copy the map once per header.
+    pub(crate) fn with_header_changes(
+        &self,
+        change: impl FnOnce(&mut HeaderMap) -> Result<(), &'static str>,
+    ) -> Result<Self, &'static str> {
+        let mut message = self.clone();
+        change(&mut message.headers)?;
+        Ok(message)
+    }
+
+    pub(crate) fn with_header(
+        &self,
+        name: &str,
+        values: Vec<String>,
+    ) -> Result<Self, &'static str> {
+        self.with_header_changes(|headers| headers.set(name, values))
+    }
+
+    // Keeps a header that is already present, whatever its case. The new
+    // value is validated either way.
+    pub(crate) fn with_header_if_none(
+        &self,
+        name: &str,
+        values: Vec<String>,
+    ) -> Result<Self, &'static str> {
+        let message = self.with_header(name, values)?;
+        Ok(if self.headers.contains(name) {
+            self.clone()
+        } else {
+            message
+        })
+    }
+
+    pub(crate) fn with_headers(
+        &self,
+        entries: Vec<(String, Vec<String>)>,
+    ) -> Result<Self, &'static str> {
+        self.with_header_changes(|headers| headers.set_all(entries))
+    }
+
+    pub(crate) fn with_added_header(
+        &self,
+        name: &str,
+        values: Vec<String>,
+    ) -> Result<Self, &'static str> {
+        self.with_header_changes(|headers| headers.append(name, values))
+    }
+
+    pub(crate) fn without_header(&self, name: &str) -> Self {
+        let mut message = self.clone();
+        message.headers.remove(name);
+        message
+    }
+
+    // Drops every header whose name starts with `prefix`, ignoring case.
+    pub(crate) fn without_header_matching(&self, prefix: &str) -> Self {
+        let mut message = self.clone();
+        message.headers.remove_prefix(prefix);
+        message
+    }
+
+    pub(crate) fn with_cloned_body(&self) -> io::Result<Self> {
+        Ok(Self {
+            body: self.body.deep_clone()?,
+            ..self.clone()
+        })
+    }
+
+    pub(crate) fn with_body(&self, body: &Stream) -> Self {
+        Self {
+            body: body.clone(),
+            ..self.clone()
+        }
+    }
+}
+
+pub(crate) fn fields_table(fields: &HeaderMap) -> PhpResult<ZBox<ZendHashTable>> {
+    let mut table = ZendHashTable::new();
+    for (name, values) in fields.iter() {
+        table.insert(name, values.to_vec())?;
+    }
+    Ok(table)
+}
+
+fn push_fields(raw: &mut Vec<u8>, fields: &HeaderMap) {
+    for (name, values) in fields.iter() {
+        if name.eq_ignore_ascii_case("Set-Cookie") {
+            for value in values {
+                raw.extend(format!("{name}: {value}\r\n").into_bytes());
+            }
+        } else {
+            raw.extend(format!("{name}: {}\r\n", values.join(", ")).into_bytes());
+        }
+    }
+}
+
+// Header values may be given as a single string or a list of strings.
+pub(crate) fn header_values(value: &Zval) -> PhpResult<Vec<String>> {
+    let values = match value.array() {
+        Some(array) => array
+            .values()
+            .map(scalar_string)
+            .collect::<Option<Vec<_>>>(),
+        None => scalar_string(value).map(|value| vec![value]),
+    };
+    match values {
+        Some(values) if !values.is_empty() => Ok(values),
+        _ => Err(PhpException::new(
+            "Header values must be a string or a non-empty array of strings".into(),
+            0,
+            invalid_argument_exception(),
+        )),
+    }
+}
+
+// The name and values of each entry of a `[name => value(s)]` array.
+pub(crate) fn header_entries(array: &ZendHashTable) -> PhpResult<Vec<(String, Vec<String>)>> {
+    array
+        .iter()
+        .map(|(name, value)| Ok((name.to_string(), header_values(value)?)))
+        .collect()
+}
+
+pub(crate) fn headers_from_array(array: &ZendHashTable) -> PhpResult<HeaderMap> {
+    headers_from_entries(header_entries(array)?)
+        .map_err(|err| PhpException::new(err.into(), 0, invalid_argument_exception()))
+}
+
+// Names that differ only in case are coalesced under the first one seen,
+// with their values in order.
+fn headers_from_entries(entries: Vec<(String, Vec<String>)>) -> Result<HeaderMap, &'static str> {
+    let mut headers = HeaderMap::new();
+    for (name, values) in entries {
+        headers.append(&name, values)?;
+    }
+    Ok(headers)
+}
+
+fn scalar_string(value: &Zval) -> Option<String> {
+    if let Some(long) = value.long() {
+        Some(long.to_string())
+    } else if let Some(double) = value.double() {
+        Some(double.to_string())
+    } else {
+        value.string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    fn message(headers: &[(&str, &[&str])]) -> Message {
+        let mut map = HeaderMap::new();
+        for (name, value) in headers {
+            map.append(name, values(value)).unwrap();
+        }
+        Message::new(map, None, None)
+    }
+
+    #[test]
+    fn headers_from_mixed_case_entries() {
+        let headers = headers_from_entries(vec![
+            ("Content-Type".into(), values(&["text/html"])),
+            ("X-Other".into(), values(&["1"])),
+            ("content-type".into(), values(&["charset=utf-8"])),
+        ])
+        .unwrap();
+        assert_eq!(headers.len(), 2);
+        assert_eq!(
+            headers.iter().next(),
+            Some(("Content-Type", &values(&["text/html", "charset=utf-8"])[..]))
+        );
+        assert_eq!(headers.get("CONTENT-TYPE"), ["text/html", "charset=utf-8"]);
+
+        assert!(headers_from_entries(vec![("Bad Name".into(), values(&["x"]))]).is_err());
+    }
+
+    #[test]
+    fn with_headers() {
+        let original = message(&[("Accept", &["*/*"]), ("X-Keep", &["1"])]);
+        let message = original
+            .with_headers(vec![
+                ("accept".into(), values(&["text/html"])),
+                ("X-New".into(), values(&["a", "b"])),
+            ])
+            .unwrap();
+        assert_eq!(message.headers.get("Accept"), ["text/html"]);
+        assert_eq!(message.headers.get("X-Keep"), ["1"]);
+        assert_eq!(message.headers.get("x-new"), ["a", "b"]);
+        assert_eq!(original.headers.get("Accept"), ["*/*"]);
+    }
+
+    #[test]
+    fn header_changes_match_chained() {
+        let entries: Vec<(String, Vec<String>)> = (0..20)
+            .map(|i| (format!("X-Header-{i}"), vec![i.to_string()]))
+            .collect();
+        let original = message(&[("Accept", &["*/*"])]);
+
+        let mut chained = original.clone();
+        for (name, values) in &entries {
+            chained = chained.with_added_header(name, values.clone()).unwrap();
+        }
+        let built = original
+            .with_header_changes(|headers| {
+                for (name, values) in entries.clone() {
+                    headers.append(&name, values)?;
+                }
+                Ok(())
+            })
+            .unwrap();
+        let batched = original.with_headers(entries).unwrap();
+
+        assert_eq!(built.headers, chained.headers);
+        assert_eq!(batched.headers, chained.headers);
+        assert_eq!(built.headers.len(), 21);
+        assert_eq!(original.headers.len(), 1);
+    }
+
+    #[test]
+    fn with_headers_invalid_entry() {
+        let original = message(&[("Accept", &["*/*"])]);
+        let result = original.with_headers(vec![
+            ("Accept".into(), values(&["text/html"])),
+            ("X Bad".into(), values(&["a"])),
+            ("X-New".into(), values(&["b"])),
+        ]);
+        assert!(result.is_err());
+        assert_eq!(original.headers.get("Accept"), ["*/*"]);
+        assert!(!original.headers.contains("X-New"));
+    }
+
+    #[test]
+    fn with_header_if_none() {
+        let original = message(&[("Content-Type", &["text/html"])]);
+        let message = original
+            .with_header_if_none("Cache-Control", values(&["no-store"]))
+            .unwrap();
+        assert_eq!(message.headers.get("Cache-Control"), ["no-store"]);
+        assert!(!original.headers.contains("Cache-Control"));
+
+        let message = original
+            .with_header_if_none("content-type", values(&["application/json"]))
+            .unwrap();
+        assert_eq!(message.headers.get("Content-Type"), ["text/html"]);
+        assert_eq!(original.headers.get("Content-Type"), ["text/html"]);
+
+        assert!(original
+            .with_header_if_none("Content-Type", values(&["a\r\nb"]))
+            .is_err());
+    }
+
+    #[test]
+    fn with_cloned_body() {
+        let original = message(&[]).with_body(&Stream::from_bytes(b"payload".to_vec()));
+        original.body.read(3).unwrap();
+        let copy = original.with_cloned_body().unwrap();
+        assert_eq!(*copy.body.get_contents().unwrap(), b"load");
+        assert_eq!(original.body.tell().unwrap(), 3);
+        assert_eq!(*original.body.read(2).unwrap(), b"lo");
+        assert_eq!(*copy.body.to_string(), b"payload");
+        assert_eq!(original.body.tell().unwrap(), 5);
+    }
+
+    #[test]
+    fn without_header_matching() {
+        let original = message(&[
+            ("Sec-Fetch-Mode", &["cors"]),
+            ("sec-ch-ua", &["x"]),
+            ("Accept", &["*/*"]),
+        ]);
+        let message = original.without_header_matching("sec-");
+        assert!(!message.headers.contains("Sec-Fetch-Mode"));
+        assert!(!message.headers.contains("Sec-CH-UA"));
+        assert_eq!(message.headers.get("Accept"), ["*/*"]);
+        assert!(original.headers.contains("Sec-Fetch-Mode"));
+    }
+
+    #[test]
+    fn to_raw_chunked() {
+        let message = message(&[("Content-Length", &["5"])])
+            .with_body(&Stream::from_bytes(b"hello".to_vec()))
+            .with_chunked_body()
+            .unwrap()
+            .with_trailer("X-Checksum", values(&["abc"]))
+            .unwrap();
+        assert!(!message.headers.contains("Content-Length"));
+        assert_eq!(
+            message.to_raw("HTTP/1.1 200 OK").unwrap(),
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+              5\r\nhello\r\n0\r\nX-Checksum: abc\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn to_raw_chunked_in_chunks() {
+        let body = vec![b'a'; CHUNK_SIZE + 1];
+        let message = message(&[("Transfer-Encoding", &["gzip, chunked"])])
+            .with_body(&Stream::from_bytes(body));
+        let raw = message.to_raw("HTTP/1.1 200 OK").unwrap();
+        let mut expected = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: gzip, chunked\r\n\r\n\
+              2000\r\n"
+            .to_vec();
+        expected.extend(vec![b'a'; CHUNK_SIZE]);
+        expected.extend(b"\r\n1\r\na\r\n0\r\n\r\n");
+        assert_eq!(raw, expected);
+    }
+
+    #[test]
+    fn to_raw_not_chunked() {
+        let message = message(&[("Transfer-Encoding", &["chunked, gzip"])])
+            .with_body(&Stream::from_bytes(b"hello".to_vec()));
+        assert!(message
+            .to_raw("HTTP/1.1 200 OK")
+            .unwrap()
+            .ends_with(b"\r\n\r\nhello"));
+    }
+}
@@ -0,0 +1,319 @@
+use crate::class::uploaded_file::{UploadedFile, UPLOAD_ERR_NO_FILE, UPLOAD_ERR_OK};
+use crate::header_map::parameters;
+use crate::query::{insert_param, int_key, parse_key, QueryValue};
+use ext_php_rs::boxed::ZBox;
+use ext_php_rs::convert::IntoZval;
+use ext_php_rs::types::ZendHashTable;
+
+// File parts arranged by their field names, like `$_FILES` after guzzle's
+// normalization.
+pub(crate) enum FileTree {
+    File(UploadedFile),
+    Array(Vec<(String, FileTree)>),
+}
+
+impl FileTree {
+    pub(crate) fn entries_to_array(
+        entries: &[(String, Self)],
+    ) -> ext_php_rs::error::Result<ZBox<ZendHashTable>> {
+        let mut array = ZendHashTable::new();
+        for (key, value) in entries {
+            let value = match value {
+                Self::File(file) => file.clone().into_zval(false)?,
+                Self::Array(entries) => Self::entries_to_array(entries)?.into_zval(false)?,
+            };
+            match int_key(key) {
+                Some(index) => array.insert_at_index(index, value)?,
+                None => array.insert(key, value)?,
+            }
+        }
+        Ok(array)
+    }
+}
+
+pub(crate) struct FormData {
+    pub(crate) fields: Vec<(String, QueryValue)>,
+    pub(crate) files: Vec<(String, FileTree)>,
+}
+
+type Headers = Vec<(String, String)>;
+
+/// The fields and files of a `multipart/form-data` body (RFC 7578), nested
+/// by the bracket syntax of their names the way PHP fills `$_POST` and
+/// `$_FILES`. A part without a filename is a field.
+pub(crate) fn parse_form_data(body: &[u8], boundary: &str) -> Result<FormData, &'static str> {
+    let mut fields = Vec::new();
+    let mut files = Vec::new();
+    for part in parts(body, boundary)? {
+        let (headers, contents) = split_headers(part)?;
+        let disposition = headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("Content-Disposition"))
+            .map(|(_, value)| keyed_parameters(value))
+            .ok_or("Multipart part without Content-Disposition")?;
+        let param = |name: &str| {
+            disposition
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value.clone())
+        };
+        let Some(path) = param("name").as_deref().and_then(parse_key) else {
+            continue;
+        };
+        match param("filename") {
+            None => insert_param(
+                &mut fields,
+                &path,
+                String::from_utf8_lossy(contents).into_owned(),
+            ),
+            Some(filename) => {
+                let media_type = headers
+                    .iter()
+                    .find(|(name, _)| name.eq_ignore_ascii_case("Content-Type"))
+                    .map(|(_, value)| value.to_string());
+                insert_file(
+                    &mut files,
+                    &path,
+                    uploaded_file(filename, media_type, contents),
+                );
+            }
+        }
+    }
+    Ok(FormData { fields, files })
+}
+
+/// The boundary parameter of a `multipart/form-data` Content-Type.
+pub(crate) fn boundary(content_type: &str) -> Option<String> {
+    let (media_type, params) = content_type.split_once(';')?;
+    if !media_type
+        .trim()
+        .eq_ignore_ascii_case("multipart/form-data")
+    {
+        return None;
+    }
+    keyed_parameters(params)
+        .into_iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case("boundary"))
+        .map(|(_, value)| value)
+        .filter(|boundary| !boundary.is_empty() && boundary.len() <= 70)
+}
+
+// PHP reports an empty filename as a file input left empty.
+fn uploaded_file(filename: String, media_type: Option<String>, contents: &[u8]) -> UploadedFile {
+    let error = if filename.is_empty() {
+        UPLOAD_ERR_NO_FILE
+    } else {
+        UPLOAD_ERR_OK
+    };
+    UploadedFile::from_contents(contents.to_vec(), error, Some(filename), media_type)
+}
+
+// The bodies of the parts between the delimiters, without the preamble
+// and epilogue (RFC 2046, section 5.1.1).
+fn parts<'a>(body: &'a [u8], boundary: &str) -> Result<Vec<&'a [u8]>, &'static str> {
+    let delimiter = format!("\r\n--{boundary}");
+    let delimiter = delimiter.as_bytes();
+    // The first delimiter may start the body, without a line break.
+    let mut rest = match body.strip_prefix(&delimiter[2..]) {
+        Some(rest) => rest,
+        None => {
+            let start = find(body, delimiter).ok_or("Multipart boundary not found")?;
+            &body[start + delimiter.len()..]
+        }
+    };
+    let mut parts = Vec::new();
+    loop {
+        if rest.starts_with(b"--") {
+            return Ok(parts);
+        }
+        let line_end = find(rest, b"\r\n").ok_or("Malformed multipart delimiter")?;
+        if !rest[..line_end]
+            .iter()
+            .all(|byte| matches!(byte, b' ' | b'\t'))
+        {
+            return Err("Malformed multipart delimiter");
+        }
+        rest = &rest[line_end + 2..];
+        let end = find(rest, delimiter).ok_or("Multipart body is not terminated")?;
+        parts.push(&rest[..end]);
+        rest = &rest[end + delimiter.len()..];
+    }
+}
+
+fn split_headers(part: &[u8]) -> Result<(Headers, &[u8]), &'static str> {
+    let (head, contents) = match part.strip_prefix(b"\r\n") {
+        Some(contents) => (&b""[..], contents),
+        None => {
+            let end = find(part, b"\r\n\r\n").ok_or("Malformed multipart headers")?;
+            (&part[..end], &part[end + 4..])
+        }
+    };
+    let head = std::str::from_utf8(head).map_err(|_| "Malformed multipart headers")?;
+    let headers = head
+        .split("\r\n")
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let (name, value) = line.split_once(':').ok_or("Malformed multipart headers")?;
+            Ok((name.trim().to_string(), value.trim().to_string()))
+        })
+        .collect::<Result<_, &'static str>>()?;
+    Ok((headers, contents))
+}
+
+fn keyed_parameters(value: &str) -> Vec<(String, String)> {
+    parameters(value)
+        .into_iter()
+        .filter_map(|(key, value)| Some((key?, value)))
+        .collect()
+}
+
+fn insert_file(entries: &mut Vec<(String, FileTree)>, path: &[Option<String>], file: UploadedFile) {
+    let Some((segment, rest)) = path.split_first() else {
+        return;
+    };
+    let key = match segment {
+        Some(key) => key.clone(),
+        None => entries
+            .iter()
+            .filter_map(|(key, _)| int_key(key))
+            .max()
+            .map_or(0, |max| max + 1)
+            .to_string(),
+    };
+    let pos = match entries.iter().position(|(existing, _)| *existing == key) {
+        Some(pos) => pos,
+        None => {
+            entries.push((key, FileTree::Array(Vec::new())));
+            entries.len() - 1
+        }
+    };
+    if rest.is_empty() {
+        entries[pos].1 = FileTree::File(file);
+        return;
+    }
+    if !matches!(entries[pos].1, FileTree::Array(_)) {
+        entries[pos].1 = FileTree::Array(Vec::new());
+    }
+    if let FileTree::Array(inner) = &mut entries[pos].1 {
+        insert_file(inner, rest, file);
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scalar(value: &str) -> QueryValue {
+        QueryValue::Scalar(value.to_string())
+    }
+
+    fn file<'a>(files: &'a [(String, FileTree)], key: &str) -> &'a UploadedFile {
+        match files.iter().find(|(name, _)| name == key) {
+            Some((_, FileTree::File(file))) => file,
+            _ => panic!("no file at {key}"),
+        }
+    }
+
+    const BODY: &[u8] = b"preamble\r\n\
+        --XyZ\r\n\
+        Content-Disposition: form-data; name=\"title\"\r\n\
+        \r\n\
+        Hello\r\nworld\r\n\
+        --XyZ\r\n\
+        Content-Disposition: form-data; name=\"doc\"; filename=\"a \\\"b\\\".txt\"\r\n\
+        Content-Type: text/plain\r\n\
+        \r\n\
+        file contents\r\n\
+        --XyZ--\r\n\
+        epilogue";
+
+    #[test]
+    fn field_and_file() {
+        let FormData { fields, files } = parse_form_data(BODY, "XyZ").unwrap();
+        assert_eq!(fields, [("title".to_string(), scalar("Hello\r\nworld"))]);
+        let doc = file(&files, "doc");
+        assert_eq!(doc.get_client_filename().as_deref(), Some("a \"b\".txt"));
+        assert_eq!(doc.get_client_media_type().as_deref(), Some("text/plain"));
+        assert_eq!(doc.get_size(), Some(13));
+        assert_eq!(doc.get_error(), UPLOAD_ERR_OK);
+        assert_eq!(*doc.get_stream().unwrap().to_string(), b"file contents");
+    }
+
+    #[test]
+    fn nested_names_and_multiple_files() {
+        let body = b"--b\r\n\
+            Content-Disposition: form-data; name=\"user[name]\"\r\n\r\nalice\r\n\
+            --b\r\n\
+            Content-Disposition: form-data; name=\"user[tags][]\"\r\n\r\nx\r\n\
+            --b\r\n\
+            Content-Disposition: form-data; name=\"user[tags][]\"\r\n\r\ny\r\n\
+            --b\r\n\
+            Content-Disposition: form-data; name=\"photos[]\"; filename=\"1.jpg\"\r\n\r\n1\r\n\
+            --b\r\n\
+            Content-Disposition: form-data; name=\"photos[]\"; filename=\"2.jpg\"\r\n\r\n22\r\n\
+            --b\r\n\
+            Content-Disposition: form-data; name=\"empty\"; filename=\"\"\r\n\r\n\r\n\
+            --b--";
+        let FormData { fields, files } = parse_form_data(body, "b").unwrap();
+        assert_eq!(
+            fields,
+            [(
+                "user".to_string(),
+                QueryValue::Array(vec![
+                    ("name".to_string(), scalar("alice")),
+                    (
+                        "tags".to_string(),
+                        QueryValue::Array(vec![
+                            ("0".to_string(), scalar("x")),
+                            ("1".to_string(), scalar("y")),
+                        ])
+                    ),
+                ])
+            )]
+        );
+        let Some((_, FileTree::Array(photos))) = files.iter().find(|(key, _)| key == "photos")
+        else {
+            panic!("photos is not an array");
+        };
+        assert_eq!(
+            file(photos, "0").get_client_filename().as_deref(),
+            Some("1.jpg")
+        );
+        assert_eq!(file(photos, "1").get_size(), Some(2));
+        assert_eq!(file(&files, "empty").get_error(), UPLOAD_ERR_NO_FILE);
+    }
+
+    #[test]
+    fn malformed() {
+        let cases: [&[u8]; 4] = [
+            b"no boundary here",
+            b"--b\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\nunterminated",
+            b"--b\r\nContent-Disposition: form-data; name=\"a\"\r\nno blank line\r\n--b--",
+            b"--b\r\nX-Other: 1\r\n\r\nvalue\r\n--b--",
+        ];
+        for body in cases {
+            assert!(parse_form_data(body, "b").is_err(), "body: {body:?}");
+        }
+    }
+
+    #[test]
+    fn boundary_from_content_type() {
+        assert_eq!(
+            boundary("multipart/form-data; boundary=abc").as_deref(),
+            Some("abc")
+        );
+        assert_eq!(
+            boundary("Multipart/Form-Data; charset=utf-8; boundary=\"a b;c\"").as_deref(),
+            Some("a b;c")
+        );
+        assert_eq!(boundary("multipart/form-data"), None);
+        assert_eq!(boundary("multipart/mixed; boundary=abc"), None);
+        assert_eq!(boundary("multipart/form-data; boundary="), None);
+    }
+}
@@ -0,0 +1,126 @@
+use crate::idn;
+use std::collections::HashSet;
+use std::net::Ipv4Addr;
+use std::sync::OnceLock;
+
+// A snapshot of https://publicsuffix.org/list/public_suffix_list.dat,
+// including private domains.
+const LIST: &str = include_str!("public_suffix_list.dat");
+
+static RULES: OnceLock<Rules> = OnceLock::new();
+
+// Rules are stored in their ASCII (punycode) form, without the `*.` of
+// wildcard rules and the `!` of exception rules.
+struct Rules {
+    normal: HashSet<String>,
+    wildcard: HashSet<String>,
+    exception: HashSet<String>,
+}
+
+fn rules() -> &'static Rules {
+    RULES.get_or_init(|| {
+        let mut rules = Rules {
+            normal: HashSet::new(),
+            wildcard: HashSet::new(),
+            exception: HashSet::new(),
+        };
+        let lines = LIST
+            .lines()
+            .filter_map(|line| line.split_whitespace().next())
+            .filter(|rule| !rule.starts_with("//"));
+        for rule in lines {
+            let (set, rule) = if let Some(rule) = rule.strip_prefix("*.") {
+                (&mut rules.wildcard, rule)
+            } else if let Some(rule) = rule.strip_prefix('!') {
+                (&mut rules.exception, rule)
+            } else {
+                (&mut rules.normal, rule)
+            };
+            if let Some(rule) = idn::to_ascii(rule) {
+                set.insert(rule.to_lowercase());
+            }
+        }
+        rules
+    })
+}
+
+/// Returns the public suffix of `host`, a lowercased ASCII host, following
+/// the algorithm at https://publicsuffix.org/list/. Unlisted TLDs are
+/// public suffixes themselves. IP addresses have none.
+pub(crate) fn public_suffix(host: &str) -> Option<&str> {
+    if host.is_empty()
+        || host.starts_with('[')
+        || host.parse::<Ipv4Addr>().is_ok()
+        || host.split('.').any(str::is_empty)
+    {
+        return None;
+    }
+    let rules = rules();
+    let suffixes: Vec<&str> = std::iter::once(host)
+        .chain(host.match_indices('.').map(|(pos, _)| &host[pos + 1..]))
+        .collect();
+    if let Some(suffix) = suffixes
+        .iter()
+        .find(|suffix| rules.exception.contains(**suffix))
+    {
+        return suffix.split_once('.').map(|(_, parent)| parent);
+    }
+    let matched = suffixes.iter().find(|suffix| {
+        rules.normal.contains(**suffix)
+            || suffix
+                .split_once('.')
+                .is_some_and(|(_, parent)| rules.wildcard.contains(parent))
+    });
+    Some(matched.copied().unwrap_or(suffixes[suffixes.len() - 1]))
+}
+
+/// Returns the public suffix of `host` plus one label, if `host` has more
+/// labels than its public suffix.
+pub(crate) fn registrable_domain(host: &str) -> Option<&str> {
+    let suffix = public_suffix(host)?;
+    let prefix = host.strip_suffix(suffix)?.strip_suffix('.')?;
+    let start = prefix.rfind('.').map_or(0, |pos| pos + 1);
+    Some(&host[start..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn public_suffixes() {
+        let cases = [
+            ("example.com", Some("com")),
+            ("www.example.co.uk", Some("co.uk")),
+            ("co.uk", Some("co.uk")),
+            ("foo.example.unlisted", Some("unlisted")),
+            ("a.b.ck", Some("b.ck")),
+            ("www.ck", Some("ck")),
+            ("xn--85x722f.xn--55qx5d.cn", Some("xn--55qx5d.cn")),
+            ("127.0.0.1", None),
+            ("[::1]", None),
+            ("", None),
+            ("example..com", None),
+        ];
+        for (host, expected) in cases {
+            assert_eq!(public_suffix(host), expected, "host: {host}");
+        }
+    }
+
+    #[test]
+    fn registrable_domains() {
+        let cases = [
+            ("example.com", Some("example.com")),
+            ("a.b.example.com", Some("example.com")),
+            ("www.example.co.uk", Some("example.co.uk")),
+            ("co.uk", None),
+            ("com", None),
+            ("www.ck", Some("www.ck")),
+            ("a.b.ck", Some("a.b.ck")),
+            ("www.city.kawasaki.jp", Some("city.kawasaki.jp")),
+        ];
+        for (host, expected) in cases {
+            assert_eq!(registrable_domain(host), expected, "host: {host}");
+        }
+    }
+}
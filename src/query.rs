@@ -0,0 +1,536 @@
+use crate::util::{percent_decode, push_percent_encoded};
+use ext_php_rs::boxed::ZBox;
+use ext_php_rs::types::{ZendHashTable, Zval};
+
+pub(crate) const PHP_QUERY_RFC1738: i64 = 1;
+pub(crate) const PHP_QUERY_RFC3986: i64 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum QueryEncoding {
+    Rfc1738,
+    Rfc3986,
+}
+
+impl TryFrom<i64> for QueryEncoding {
+    type Error = &'static str;
+
+    fn try_from(value: i64) -> Result<Self, Self::Error> {
+        match value {
+            PHP_QUERY_RFC1738 => Ok(Self::Rfc1738),
+            PHP_QUERY_RFC3986 => Ok(Self::Rfc3986),
+            _ => Err("Invalid query encoding"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum QueryValue {
+    Scalar(String),
+    Array(Vec<(String, QueryValue)>),
+}
+
+impl QueryValue {
+    // Mirrors the conversions done by `http_build_query`: nulls (and
+    // objects) are skipped, booleans become "1"/"0".
+    pub(crate) fn from_zval(zval: &Zval) -> Option<Self> {
+        if let Some(array) = zval.array() {
+            Some(Self::Array(Self::entries_from_array(array)))
+        } else if let Some(bool) = zval.bool() {
+            Some(Self::Scalar(if bool { "1" } else { "0" }.to_string()))
+        } else if let Some(long) = zval.long() {
+            Some(Self::Scalar(long.to_string()))
+        } else if let Some(double) = zval.double() {
+            Some(Self::Scalar(double.to_string()))
+        } else {
+            zval.string().map(Self::Scalar)
+        }
+    }
+
+    pub(crate) fn entries_from_array(array: &ZendHashTable) -> Vec<(String, Self)> {
+        array
+            .iter()
+            .filter_map(|(key, value)| Some((key.to_string(), Self::from_zval(value)?)))
+            .collect()
+    }
+
+    // Keys that are canonical integers become integer keys, as they would
+    // in a PHP array literal.
+    pub(crate) fn entries_to_array(
+        entries: &[(String, Self)],
+    ) -> ext_php_rs::error::Result<ZBox<ZendHashTable>> {
+        let mut array = ZendHashTable::new();
+        for (key, value) in entries {
+            match (int_key(key), value) {
+                (Some(index), Self::Scalar(value)) => {
+                    array.insert_at_index(index, value.as_str())?
+                }
+                (Some(index), Self::Array(entries)) => {
+                    array.insert_at_index(index, Self::entries_to_array(entries)?)?
+                }
+                (None, Self::Scalar(value)) => array.insert(key, value.as_str())?,
+                (None, Self::Array(entries)) => {
+                    array.insert(key, Self::entries_to_array(entries)?)?
+                }
+            }
+        }
+        Ok(array)
+    }
+}
+
+pub(crate) fn int_key(key: &str) -> Option<u64> {
+    key.parse::<u64>()
+        .ok()
+        .filter(|index| index.to_string() == key)
+}
+
+/// Decodes a query string the way `parse_str()` does, including its
+/// bracket syntax for nested arrays (`a[b][]=1`).
+pub(crate) fn parse_query_params(query: &str) -> Vec<(String, QueryValue)> {
+    let mut params = Vec::new();
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        if let Some(path) = parse_key(&url_decode(key)) {
+            insert_param(&mut params, &path, url_decode(value));
+        }
+    }
+    params
+}
+
+fn url_decode(str: &str) -> String {
+    percent_decode(&str.replace('+', " "))
+}
+
+// Splits `base[a][]` into `[Some("base"), Some("a"), None]`, where `None`
+// appends. Like PHP, spaces and dots in the base name become underscores,
+// an unterminated first bracket is kept as part of the name, and anything
+// after the last well-formed bracket is ignored.
+pub(crate) fn parse_key(key: &str) -> Option<Vec<Option<String>>> {
+    let key = key.trim_start_matches(' ');
+    let (base, mut rest) = match key.find('[') {
+        Some(pos) if key[pos..].contains(']') => (&key[..pos], &key[pos..]),
+        Some(pos) => {
+            let base = key[..pos].replace([' ', '.'], "_");
+            return (pos > 0).then(|| vec![Some(format!("{}_{}", base, &key[pos + 1..]))]);
+        }
+        None => (key, ""),
+    };
+    if base.is_empty() {
+        return None;
+    }
+    let mut path = vec![Some(base.replace([' ', '.'], "_"))];
+    while let Some(inner) = rest.strip_prefix('[') {
+        let Some(end) = inner.find(']') else {
+            break;
+        };
+        let index = &inner[..end];
+        path.push((!index.is_empty()).then(|| index.to_string()));
+        rest = &inner[end + 1..];
+    }
+    Some(path)
+}
+
+pub(crate) fn insert_param(
+    entries: &mut Vec<(String, QueryValue)>,
+    path: &[Option<String>],
+    value: String,
+) {
+    let Some((segment, rest)) = path.split_first() else {
+        return;
+    };
+    let key = match segment {
+        Some(key) => key.clone(),
+        None => next_index(entries).to_string(),
+    };
+    let pos = match entries.iter().position(|(existing, _)| *existing == key) {
+        Some(pos) => pos,
+        None => {
+            entries.push((key, QueryValue::Array(Vec::new())));
+            entries.len() - 1
+        }
+    };
+    if rest.is_empty() {
+        entries[pos].1 = QueryValue::Scalar(value);
+        return;
+    }
+    if !matches!(entries[pos].1, QueryValue::Array(_)) {
+        entries[pos].1 = QueryValue::Array(Vec::new());
+    }
+    if let QueryValue::Array(inner) = &mut entries[pos].1 {
+        insert_param(inner, rest, value);
+    }
+}
+
+fn next_index(entries: &[(String, QueryValue)]) -> u64 {
+    entries
+        .iter()
+        .filter_map(|(key, _)| int_key(key))
+        .max()
+        .map_or(0, |max| max + 1)
+}
+
+pub(crate) fn build_query(params: &[(String, QueryValue)], encoding: QueryEncoding) -> String {
+    let mut pairs = Vec::new();
+    for (key, value) in params {
+        push_pairs(&mut pairs, encode(key, encoding), value, encoding);
+    }
+    pairs.join("&")
+}
+
+/// Splits a query into its pairs the way guzzle's `Query::parse()` does:
+/// repeated keys collect their values, and a key without `=` has a `None`
+/// value.
+pub(crate) fn parse_query(
+    query: &str,
+    decoding: Option<QueryEncoding>,
+) -> Vec<(String, Vec<Option<String>>)> {
+    let mut result: Vec<(String, Vec<Option<String>>)> = Vec::new();
+    if query.is_empty() {
+        return result;
+    }
+    let decode = |str: &str| match decoding {
+        Some(QueryEncoding::Rfc1738) => url_decode(str),
+        Some(QueryEncoding::Rfc3986) => percent_decode(str),
+        None => str.to_string(),
+    };
+    for pair in query.split('&') {
+        let mut parts = pair.splitn(2, '=');
+        let key = decode(parts.next().unwrap_or(""));
+        let value = parts.next().map(decode);
+        match result.iter_mut().find(|(existing, _)| *existing == key) {
+            // Like `isset()`, a lone null value does not count as set.
+            Some((_, values)) if *values == [None] => *values = vec![value],
+            Some((_, values)) => values.push(value),
+            None => result.push((key, vec![value])),
+        }
+    }
+    result
+}
+
+/// Builds a query without PHP's bracket syntax, repeating the key for
+/// each of its values, as guzzle's `Query::build()` does.
+pub(crate) fn build_query_pairs(
+    params: &[(String, Vec<Option<String>>)],
+    encoding: Option<QueryEncoding>,
+) -> String {
+    let encode = |str: &str| match encoding {
+        Some(encoding) => encode(str, encoding),
+        None => str.to_string(),
+    };
+    let mut pairs = Vec::new();
+    for (key, values) in params {
+        let key = encode(key);
+        for value in values {
+            pairs.push(match value {
+                Some(value) => format!("{}={}", key, encode(value)),
+                None => key.clone(),
+            });
+        }
+    }
+    pairs.join("&")
+}
+
+/// Replaces every pair whose decoded key is one of the given keys with the
+/// given pairs, appended after the untouched ones. A `None` value yields a
+/// bare key.
+pub(crate) fn with_query_values(query: &str, pairs: &[(String, Option<String>)]) -> String {
+    let keys: Vec<_> = pairs.iter().map(|(key, _)| key.as_str()).collect();
+    let mut result = without_keys(query, &keys);
+    for (key, value) in pairs {
+        let mut pair = escape_separators(key);
+        if let Some(value) = value {
+            pair.push('=');
+            pair.push_str(&escape_separators(value));
+        }
+        result.push(pair);
+    }
+    result.join("&")
+}
+
+pub(crate) fn without_query_value(query: &str, key: &str) -> String {
+    without_keys(query, &[key]).join("&")
+}
+
+fn without_keys(query: &str, keys: &[&str]) -> Vec<String> {
+    if query.is_empty() {
+        return Vec::new();
+    }
+    let keys: Vec<_> = keys.iter().map(|key| percent_decode(key)).collect();
+    query
+        .split('&')
+        .filter(|pair| {
+            let key = pair.split('=').next().unwrap_or("");
+            !keys.contains(&percent_decode(key))
+        })
+        .map(str::to_string)
+        .collect()
+}
+
+// Only the characters that would change the structure of the query are
+// escaped; the rest is encoded when the query is set on the URI.
+fn escape_separators(str: &str) -> String {
+    str.replace('=', "%3D").replace('&', "%26")
+}
+
+fn push_pairs(pairs: &mut Vec<String>, key: String, value: &QueryValue, encoding: QueryEncoding) {
+    match value {
+        QueryValue::Scalar(value) => pairs.push(format!("{}={}", key, encode(value, encoding))),
+        QueryValue::Array(entries) => {
+            for (sub_key, value) in entries {
+                let key = format!("{}%5B{}%5D", key, encode(sub_key, encoding));
+                push_pairs(pairs, key, value, encoding);
+            }
+        }
+    }
+}
+
+fn encode(str: &str, encoding: QueryEncoding) -> String {
+    let mut result = String::with_capacity(str.len());
+    for &byte in str.as_bytes() {
+        match (byte, encoding) {
+            (b' ', QueryEncoding::Rfc1738) => result.push('+'),
+            (b'~', QueryEncoding::Rfc3986) => result.push('~'),
+            (b'-' | b'.' | b'_', _) => result.push(byte as char),
+            _ if byte.is_ascii_alphanumeric() => result.push(byte as char),
+            _ => push_percent_encoded(&mut result, byte),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scalar(value: &str) -> QueryValue {
+        QueryValue::Scalar(value.to_string())
+    }
+
+    #[test]
+    fn build_query_flat() {
+        let params = vec![
+            ("foo".to_string(), scalar("bar")),
+            ("baz".to_string(), scalar("1")),
+        ];
+        assert_eq!(
+            build_query(&params, QueryEncoding::Rfc3986),
+            "foo=bar&baz=1"
+        );
+    }
+
+    #[test]
+    fn build_query_nested() {
+        let params = vec![(
+            "a".to_string(),
+            QueryValue::Array(vec![
+                ("0".to_string(), scalar("x")),
+                (
+                    "b".to_string(),
+                    QueryValue::Array(vec![("c".to_string(), scalar("y"))]),
+                ),
+            ]),
+        )];
+        assert_eq!(
+            build_query(&params, QueryEncoding::Rfc3986),
+            "a%5B0%5D=x&a%5Bb%5D%5Bc%5D=y"
+        );
+    }
+
+    #[test]
+    fn build_query_encoding() {
+        let params = vec![("a b".to_string(), scalar("c d~"))];
+        assert_eq!(build_query(&params, QueryEncoding::Rfc3986), "a%20b=c%20d~");
+        assert_eq!(build_query(&params, QueryEncoding::Rfc1738), "a+b=c+d%7E");
+    }
+
+    #[test]
+    fn build_query_empty() {
+        assert_eq!(build_query(&[], QueryEncoding::Rfc3986), "");
+    }
+
+    fn grouped(pairs: &[(&str, &[Option<&str>])]) -> Vec<(String, Vec<Option<String>>)> {
+        pairs
+            .iter()
+            .map(|(key, values)| {
+                let values = values.iter().map(|value| value.map(str::to_string));
+                (key.to_string(), values.collect())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parse_query_groups_duplicates() {
+        assert_eq!(
+            parse_query("a=1&b&a=2&c=&a=3", Some(QueryEncoding::Rfc1738)),
+            grouped(&[
+                ("a", &[Some("1"), Some("2"), Some("3")]),
+                ("b", &[None]),
+                ("c", &[Some("")]),
+            ])
+        );
+        assert_eq!(
+            parse_query("a&a=1", Some(QueryEncoding::Rfc1738)),
+            grouped(&[("a", &[Some("1")])])
+        );
+        assert_eq!(parse_query("", Some(QueryEncoding::Rfc1738)), vec![]);
+    }
+
+    #[test]
+    fn parse_query_decoding() {
+        let query = "a+b=c%20d";
+        assert_eq!(
+            parse_query(query, Some(QueryEncoding::Rfc1738)),
+            grouped(&[("a b", &[Some("c d")])])
+        );
+        assert_eq!(
+            parse_query(query, Some(QueryEncoding::Rfc3986)),
+            grouped(&[("a+b", &[Some("c d")])])
+        );
+        assert_eq!(
+            parse_query(query, None),
+            grouped(&[("a+b", &[Some("c%20d")])])
+        );
+    }
+
+    #[test]
+    fn build_query_pairs_repeats_keys() {
+        let params = grouped(&[("a", &[Some("1"), Some("2")]), ("b", &[None]), ("c", &[])]);
+        assert_eq!(
+            build_query_pairs(&params, Some(QueryEncoding::Rfc3986)),
+            "a=1&a=2&b"
+        );
+    }
+
+    #[test]
+    fn build_query_pairs_encoding() {
+        let params = grouped(&[("a b", &[Some("c~d")])]);
+        assert_eq!(
+            build_query_pairs(&params, Some(QueryEncoding::Rfc3986)),
+            "a%20b=c~d"
+        );
+        assert_eq!(
+            build_query_pairs(&params, Some(QueryEncoding::Rfc1738)),
+            "a+b=c%7Ed"
+        );
+        assert_eq!(build_query_pairs(&params, None), "a b=c~d");
+    }
+
+    fn pairs(pairs: &[(&str, Option<&str>)]) -> Vec<(String, Option<String>)> {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.map(str::to_string)))
+            .collect()
+    }
+
+    #[test]
+    fn with_query_values_replaces_keys() {
+        let query = "a=1&b=2&a=3&c";
+        assert_eq!(
+            with_query_values(query, &pairs(&[("a", Some("x"))])),
+            "b=2&c&a=x"
+        );
+        assert_eq!(
+            with_query_values(query, &pairs(&[("c", Some("y")), ("d", None)])),
+            "a=1&b=2&a=3&c=y&d"
+        );
+    }
+
+    #[test]
+    fn with_query_values_escapes_separators() {
+        assert_eq!(
+            with_query_values("", &pairs(&[("k=&", Some("v=&"))])),
+            "k%3D%26=v%3D%26"
+        );
+    }
+
+    #[test]
+    fn with_query_values_matches_decoded_keys() {
+        assert_eq!(
+            with_query_values("a%20b=1&c=2", &pairs(&[("a b", Some("3"))])),
+            "c=2&a b=3"
+        );
+    }
+
+    #[test]
+    fn without_query_value_removes_all() {
+        assert_eq!(without_query_value("a=1&b=2&a=3", "a"), "b=2");
+        assert_eq!(without_query_value("a=1", "a"), "");
+        assert_eq!(without_query_value("", "a"), "");
+    }
+
+    fn array(entries: &[(&str, QueryValue)]) -> QueryValue {
+        QueryValue::Array(
+            entries
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.clone()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn parse_query_params_flat() {
+        assert_eq!(
+            parse_query_params("a=1&b=x+y%21&c&=skip&a=2"),
+            vec![
+                ("a".to_string(), scalar("2")),
+                ("b".to_string(), scalar("x y!")),
+                ("c".to_string(), scalar("")),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_query_params_nested() {
+        assert_eq!(
+            parse_query_params("a[b][]=1&a[b][]=2&a[c]=3&d[]=4&d[5]=5&d[]=6"),
+            vec![
+                (
+                    "a".to_string(),
+                    array(&[
+                        ("b", array(&[("0", scalar("1")), ("1", scalar("2"))])),
+                        ("c", scalar("3")),
+                    ])
+                ),
+                (
+                    "d".to_string(),
+                    array(&[("0", scalar("4")), ("5", scalar("5")), ("6", scalar("6"))])
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_query_params_key_mangling() {
+        let keys = |query| {
+            parse_query_params(query)
+                .into_iter()
+                .map(|(key, _)| key)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(keys("a.b=1&c d=2&%20e=3"), ["a_b", "c_d", "e"]);
+        assert_eq!(keys("f.[g.h=1"), ["f__g.h"]);
+        assert_eq!(keys("[h]=1"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn parse_query_params_ignores_trailing_garbage() {
+        assert_eq!(
+            parse_query_params("a[b]c[d]=1"),
+            vec![("a".to_string(), array(&[("b", scalar("1"))]))]
+        );
+    }
+
+    #[test]
+    fn parse_query_params_scalar_replaced_by_array() {
+        assert_eq!(
+            parse_query_params("a=1&a[]=2"),
+            vec![("a".to_string(), array(&[("0", scalar("2"))]))]
+        );
+    }
+
+    #[test]
+    fn encoding_from_php_constant() {
+        assert_eq!(QueryEncoding::try_from(1), Ok(QueryEncoding::Rfc1738));
+        assert_eq!(QueryEncoding::try_from(2), Ok(QueryEncoding::Rfc3986));
+        assert!(QueryEncoding::try_from(3).is_err());
+    }
+}
@@ -1,5 +1,380 @@
+use ext_php_rs::exception::PhpException;
 use ext_php_rs::zend::ClassEntry;
 
 pub(crate) fn invalid_argument_exception() -> &'static ClassEntry {
     ClassEntry::try_find("InvalidArgumentException").unwrap()
 }
+
+pub(crate) fn runtime_exception() -> &'static ClassEntry {
+    ClassEntry::try_find("RuntimeException").unwrap()
+}
+
+pub(crate) fn stream_exception() -> &'static ClassEntry {
+    ClassEntry::try_find("Takaram\\Psr7\\Exception\\StreamException").unwrap()
+}
+
+// Raised by `__unserialize()` when the state lacks what the class needs.
+pub(crate) fn invalid_serialized_data(class: &str) -> PhpException {
+    PhpException::new(
+        format!("Invalid serialized {class} data"),
+        0,
+        invalid_argument_exception(),
+    )
+}
+
+pub(crate) fn json_serializable() -> &'static ClassEntry {
+    ClassEntry::try_find("JsonSerializable").unwrap()
+}
+
+/// Decodes every percent-encoded sequence; invalid UTF-8 is replaced.
+pub(crate) fn percent_decode(str: &str) -> String {
+    String::from_utf8_lossy(&percent_decode_bytes(str)).into_owned()
+}
+
+pub(crate) fn percent_decode_bytes(str: &str) -> Vec<u8> {
+    let bytes = str.as_bytes();
+    let mut result = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes
+            .get(i + 1..i + 3)
+            .filter(|_| is_percent_encoded(&bytes[i..]))
+        {
+            Some(hex) => {
+                let hex = std::str::from_utf8(hex).unwrap_or_default();
+                result.push(u8::from_str_radix(hex, 16).unwrap_or_default());
+                i += 3;
+            }
+            None => {
+                result.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    result
+}
+
+pub(crate) fn capitalize_percent_encoding(str: &str) -> String {
+    normalize_encoding(str, true, false)
+}
+
+pub(crate) fn decode_unreserved(str: &str) -> String {
+    normalize_encoding(str, false, true)
+}
+
+fn normalize_encoding(str: &str, capitalize: bool, decode: bool) -> String {
+    let bytes = str.as_bytes();
+    let mut result = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let decoded = match bytes.get(i..i + 3) {
+            Some([b'%', hi, lo]) if hi.is_ascii_hexdigit() && lo.is_ascii_hexdigit() => {
+                std::str::from_utf8(&[*hi, *lo])
+                    .ok()
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            }
+            _ => None,
+        };
+        match decoded {
+            Some(byte) if decode && is_unreserved(byte) => {
+                result.push(byte);
+                i += 3;
+            }
+            Some(_) if capitalize => {
+                result.push(b'%');
+                result.push(bytes[i + 1].to_ascii_uppercase());
+                result.push(bytes[i + 2].to_ascii_uppercase());
+                i += 3;
+            }
+            Some(_) => {
+                result.extend_from_slice(&bytes[i..i + 3]);
+                i += 3;
+            }
+            None => {
+                result.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&result).into_owned()
+}
+
+/// Lowercases ASCII letters, leaving the hex digits of percent-encoded
+/// sequences as they are.
+pub(crate) fn to_lowercase_preserving_encoding(str: &str) -> String {
+    let bytes = str.as_bytes();
+    let mut result = String::with_capacity(str.len());
+    let mut chars = str.char_indices();
+    while let Some((i, c)) = chars.next() {
+        result.push(c.to_ascii_lowercase());
+        if is_percent_encoded(&bytes[i..]) {
+            result.extend(chars.by_ref().take(2).map(|(_, c)| c));
+        }
+    }
+    result
+}
+
+pub(crate) fn remove_dot_segments(path: &str) -> String {
+    let mut input = path;
+    let mut output = String::with_capacity(path.len());
+    while !input.is_empty() {
+        if let Some(rest) = input.strip_prefix("../") {
+            input = rest;
+        } else if let Some(rest) = input.strip_prefix("./") {
+            input = rest;
+        } else if input.starts_with("/./") {
+            input = &input[2..];
+        } else if input == "/." {
+            input = "/";
+        } else if input.starts_with("/../") {
+            input = &input[3..];
+            output.truncate(output.rfind('/').unwrap_or(0));
+        } else if input == "/.." {
+            input = "/";
+            output.truncate(output.rfind('/').unwrap_or(0));
+        } else if input == "." || input == ".." {
+            input = "";
+        } else {
+            let start = usize::from(input.starts_with('/'));
+            let end = input[start..]
+                .find('/')
+                .map_or(input.len(), |pos| pos + start);
+            output.push_str(&input[..end]);
+            input = &input[end..];
+        }
+    }
+    output
+}
+
+// Characters allowed in each URI component on top of unreserved characters
+// and sub-delims (RFC 3986, section 3).
+pub(crate) const USER_CHARS: &[u8] = b"";
+pub(crate) const USER_INFO_CHARS: &[u8] = b":";
+pub(crate) const PATH_SEGMENT_CHARS: &[u8] = b":@";
+pub(crate) const PATH_CHARS: &[u8] = b":@/";
+pub(crate) const QUERY_CHARS: &[u8] = b":@/?";
+pub(crate) const FRAGMENT_CHARS: &[u8] = b":@/?";
+
+/// Percent-encodes every character of `component` that is not allowed in
+/// it, leaving existing percent-encoded sequences untouched.
+pub(crate) fn percent_encode(component: &str, allowed: &[u8]) -> String {
+    encode(component, allowed, true)
+}
+
+pub(crate) fn encode_path_segment(segment: &str) -> String {
+    encode(segment, PATH_SEGMENT_CHARS, false)
+}
+
+fn encode(str: &str, allowed: &[u8], keep_encoded: bool) -> String {
+    let bytes = str.as_bytes();
+    let mut result = String::with_capacity(bytes.len());
+    for (i, &byte) in bytes.iter().enumerate() {
+        if is_unreserved(byte) || is_sub_delim(byte) || allowed.contains(&byte) {
+            result.push(byte as char);
+        } else if keep_encoded && byte == b'%' && is_percent_encoded(&bytes[i..]) {
+            result.push('%');
+        } else {
+            push_percent_encoded(&mut result, byte);
+        }
+    }
+    result
+}
+
+fn is_percent_encoded(bytes: &[u8]) -> bool {
+    matches!(bytes, [b'%', hi, lo, ..] if hi.is_ascii_hexdigit() && lo.is_ascii_hexdigit())
+}
+
+pub(crate) fn push_percent_encoded(result: &mut String, byte: u8) {
+    result.push('%');
+    result.push(HEX_DIGITS[usize::from(byte >> 4)] as char);
+    result.push(HEX_DIGITS[usize::from(byte & 0x0f)] as char);
+}
+
+pub(crate) fn is_valid_component(str: &str, allowed: &[u8]) -> bool {
+    let bytes = str.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                if !is_percent_encoded(&bytes[i..]) {
+                    return false;
+                }
+                i += 3;
+            }
+            byte if is_unreserved(byte) || is_sub_delim(byte) || allowed.contains(&byte) => i += 1,
+            _ => return false,
+        }
+    }
+    true
+}
+
+// RFC 7230, section 3.2.6
+pub(crate) fn is_token(str: &str) -> bool {
+    !str.is_empty()
+        && str
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&byte))
+}
+
+const HEX_DIGITS: &[u8; 16] = b"0123456789ABCDEF";
+
+fn is_sub_delim(byte: u8) -> bool {
+    matches!(
+        byte,
+        b'!' | b'$' | b'&' | b'\'' | b'(' | b')' | b'*' | b'+' | b',' | b';' | b'='
+    )
+}
+
+pub(crate) fn is_unreserved(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~')
+}
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+// Standard alphabet, padded.
+pub(crate) fn base64_encode(input: &[u8]) -> String {
+    let mut result = String::with_capacity(input.len().div_ceil(3) * 4);
+    for chunk in input.chunks(3) {
+        let buffer = chunk.iter().enumerate().fold(0u32, |buffer, (i, &byte)| {
+            buffer | u32::from(byte) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            if i <= chunk.len() {
+                let index = (buffer >> (18 - 6 * i)) & 0x3f;
+                result.push(BASE64_ALPHABET[index as usize] as char);
+            } else {
+                result.push('=');
+            }
+        }
+    }
+    result
+}
+
+// Standard alphabet; padding is optional and whitespace is ignored.
+pub(crate) fn base64_decode(input: &[u8]) -> Option<Vec<u8>> {
+    let mut result = Vec::with_capacity(input.len() * 3 / 4);
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+    let input = input.iter().filter(|byte| !byte.is_ascii_whitespace());
+    let mut padding = false;
+    for &byte in input {
+        let value = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            b'=' => {
+                padding = true;
+                continue;
+            }
+            _ => return None,
+        };
+        if padding {
+            return None;
+        }
+        buffer = (buffer << 6) | u32::from(value);
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            result.push((buffer >> bits) as u8);
+        }
+    }
+    // A single leftover character cannot encode a whole byte.
+    (bits < 6).then_some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percent_encode_components() {
+        let cases: &[(&str, &[u8], &str)] = &[
+            ("us er", USER_CHARS, "us%20er"),
+            ("user:name@", USER_CHARS, "user%3Aname%40"),
+            ("user:p@ss", USER_INFO_CHARS, "user:p%40ss"),
+            ("/a b/c:d@e?f#g", PATH_CHARS, "/a%20b/c:d@e%3Ff%23g"),
+            ("a/b", PATH_SEGMENT_CHARS, "a%2Fb"),
+            ("a=b c&d/e?f#g[h]", QUERY_CHARS, "a=b%20c&d/e?f%23g%5Bh%5D"),
+            ("frag ment?/#", FRAGMENT_CHARS, "frag%20ment?/%23"),
+            ("é", PATH_CHARS, "%C3%A9"),
+        ];
+        for (input, allowed, expected) in cases {
+            assert_eq!(percent_encode(input, allowed), *expected, "input: {input}");
+        }
+    }
+
+    #[test]
+    fn percent_encode_keeps_encoded_sequences() {
+        let cases: &[(&str, &[u8], &str)] = &[
+            ("/a%20b", PATH_CHARS, "/a%20b"),
+            ("a%2fb", QUERY_CHARS, "a%2fb"),
+            ("100%", FRAGMENT_CHARS, "100%25"),
+            ("%zz", USER_CHARS, "%25zz"),
+        ];
+        for (input, allowed, expected) in cases {
+            assert_eq!(percent_encode(input, allowed), *expected, "input: {input}");
+        }
+    }
+
+    #[test]
+    fn percent_encoding_normalizations() {
+        assert_eq!(capitalize_percent_encoding("%7e%2f%41"), "%7E%2F%41");
+        assert_eq!(decode_unreserved("%7e%2f%41"), "~%2fA");
+    }
+
+    #[test]
+    fn decode() {
+        assert_eq!(percent_decode("a%20b%2F%c3%a9"), "a b/é");
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%zz%4"), "%zz%4");
+    }
+
+    #[test]
+    fn lowercase_preserving_encoding() {
+        assert_eq!(
+            to_lowercase_preserving_encoding("ExAmple.COM"),
+            "example.com"
+        );
+        assert_eq!(
+            to_lowercase_preserving_encoding("A%C3%A9B%c3"),
+            "a%C3%A9b%c3"
+        );
+        assert_eq!(to_lowercase_preserving_encoding("Ü%zZ"), "Ü%zz");
+    }
+
+    #[test]
+    fn token() {
+        assert!(is_token("GET"));
+        assert!(is_token("X-Custom_Header.1"));
+        assert!(!is_token(""));
+        assert!(!is_token("GET /"));
+        assert!(!is_token("Header:"));
+    }
+
+    #[test]
+    fn encode_path_segment_encodes_percent() {
+        assert_eq!(encode_path_segment("a%20b"), "a%2520b");
+    }
+
+    #[test]
+    fn base64_round_trip() {
+        let cases: [(&[u8], &str); 5] = [
+            (b"", ""),
+            (b"f", "Zg=="),
+            (b"fo", "Zm8="),
+            (b"foo", "Zm9v"),
+            (b"\x00\xff\xfe?", "AP/+Pw=="),
+        ];
+        for (bytes, encoded) in cases {
+            assert_eq!(base64_encode(bytes), encoded);
+            assert_eq!(base64_decode(encoded.as_bytes()).unwrap(), bytes);
+        }
+        assert_eq!(base64_decode(b"Zm9v\r\nYg").unwrap(), b"foob");
+        for invalid in ["Zm9v!", "Zg==Zg", "Z"] {
+            assert!(base64_decode(invalid.as_bytes()).is_none(), "{invalid}");
+        }
+    }
+}
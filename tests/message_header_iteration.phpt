@@ -0,0 +1,28 @@
+--TEST--
+Messages count their headers and iterate them as name => values in order
+--EXTENSIONS--
+psr7_rust
+--FILE--
+<?php
+$request = (new Takaram\Psr7\Internal\Request('GET', 'https://example.com/'))
+    ->withHeader('Accept', 'text/html')
+    ->withAddedHeader('accept', '*/*')
+    ->withHeader('X-Id', '1');
+
+var_dump($request->countHeaders());
+foreach ($request as $name => $values) {
+    echo $name, ': ', implode(', ', $values), "\n";
+}
+
+// The iterator keeps the headers it started with.
+$iterator = $request->getIterator();
+$request = $request->withoutHeader('Accept');
+var_dump(iterator_count($iterator), $request->countHeaders());
+?>
+--EXPECT--
+int(3)
+Host: example.com
+Accept: text/html, */*
+X-Id: 1
+int(3)
+int(2)
@@ -0,0 +1,127 @@
+--TEST--
+Every with* method of Request, Response and ServerRequest leaves the original unchanged
+--EXTENSIONS--
+psr7_rust
+--FILE--
+<?php
+use Takaram\Psr7\Internal\Request;
+use Takaram\Psr7\Internal\Response;
+use Takaram\Psr7\Internal\ServerRequest;
+use Takaram\Psr7\Internal\Stream;
+use Takaram\Psr7\Internal\UploadedFile;
+use Takaram\Psr7\Internal\Uri;
+
+// Everything observable about a message; the body is read from the start.
+function state(object $message): string
+{
+    $state = [
+        $message->getProtocolVersion(),
+        $message->getHeaders(),
+        (string) $message->getBody(),
+    ];
+    if (method_exists($message, 'getMethod')) {
+        $state[] = [
+            $message->getMethod(),
+            $message->getRequestTarget(),
+            (string) $message->getUri(),
+        ];
+    }
+    if ($message instanceof Response) {
+        $state[] = [
+            $message->getStatusCode(),
+            $message->getReasonPhrase(),
+            $message->getTrailers(),
+        ];
+    }
+    if ($message instanceof ServerRequest) {
+        $state[] = [
+            $message->getCookieParams(),
+            $message->getQueryParams(),
+            array_keys($message->getUploadedFiles()),
+            $message->getParsedBody(),
+            $message->getAttributes(),
+        ];
+    }
+    return serialize($state);
+}
+
+function check(string $class, object $original, array $withers): void
+{
+    $before = state($original);
+    foreach ($withers as $name => $with) {
+        $copy = $with($original);
+        if ($copy === $original || !$copy instanceof $class) {
+            echo "$class::$name did not return a new $class\n";
+        }
+        if (state($original) !== $before) {
+            echo "$class::$name changed the original\n";
+        }
+        if (state($copy) === $before) {
+            echo "$class::$name changed nothing\n";
+        }
+    }
+    echo "$class: ", count($withers), " methods checked\n";
+}
+
+$messageWithers = [
+    'withProtocolVersion' => fn ($m) => $m->withProtocolVersion('2'),
+    'withHeader' => fn ($m) => $m->withHeader('Accept', 'text/html'),
+    'withHeaderIfNone' => fn ($m) => $m->withHeaderIfNone('X-New', '1'),
+    'withHeaders' => fn ($m) => $m->withHeaders(['X-A' => '1', 'x-b' => ['2', '3']]),
+    'withAddedHeader' => fn ($m) => $m->withAddedHeader('x-id', '2'),
+    'withoutHeader' => fn ($m) => $m->withoutHeader('X-Id'),
+    'withoutHeaderMatching' => fn ($m) => $m->withoutHeaderMatching('x-'),
+    'withBody' => fn ($m) => $m->withBody(new Stream('other')),
+];
+$requestWithers = $messageWithers + [
+    'withMethod' => fn ($m) => $m->withMethod('PUT'),
+    'withRequestTarget' => fn ($m) => $m->withRequestTarget('*'),
+    'withUri' => fn ($m) => $m->withUri(new Uri('http://other.example/x')),
+];
+
+$headers = ['X-Id' => '1', 'Content-Type' => 'multipart/form-data; boundary=b'];
+$multipart = "--b\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\n1\r\n--b--";
+
+check(Request::class, new Request('POST', 'http://example.com/p', $headers, new Stream('body')), $requestWithers);
+
+check(Response::class, new Response(200, $headers, new Stream('body')), $messageWithers + [
+    'withStatus' => fn ($m) => $m->withStatus(404),
+    'withChunkedBody' => fn ($m) => $m->withChunkedBody(),
+    'withTrailer' => fn ($m) => $m->withTrailer('X-Checksum', 'abc'),
+]);
+
+$file = tempnam(sys_get_temp_dir(), 'psr7');
+$serverRequest = (new ServerRequest('POST', 'http://example.com/p', $headers, new Stream($multipart)))
+    ->withAttribute('a', 1);
+check(ServerRequest::class, $serverRequest, $requestWithers + [
+    'withCookieParams' => fn ($m) => $m->withCookieParams(['c' => '1']),
+    'withQueryParams' => fn ($m) => $m->withQueryParams(['q' => '1']),
+    'withUploadedFiles' => fn ($m) => $m->withUploadedFiles(['f' => new UploadedFile($file, 0, UPLOAD_ERR_OK)]),
+    'withParsedBody' => fn ($m) => $m->withParsedBody(['p' => '1']),
+    'withParsedMultipartBody' => fn ($m) => $m->withParsedMultipartBody(),
+    'withAttribute' => fn ($m) => $m->withAttribute('b', 2),
+    'withoutAttribute' => fn ($m) => $m->withoutAttribute('a'),
+]);
+unlink($file);
+
+// Copies share the body stream, as PSR-7 streams are not immutable, until
+// one of them takes its own copy with withClonedBody().
+$body = new Stream('shared');
+$request = new Request('POST', '/', [], $body);
+$shared = $request->withHeader('X-Id', '1');
+$cloned = $request->withClonedBody();
+$shared->getBody()->rewind();
+$shared->getBody()->write('!');
+var_dump((string) $request->getBody(), (string) $cloned->getBody());
+$cloned->getBody()->rewind();
+$cloned->getBody()->write('?');
+var_dump((string) $request->getBody(), (string) $cloned->getBody());
+?>
+--EXPECT--
+Takaram\Psr7\Internal\Request: 11 methods checked
+Takaram\Psr7\Internal\Response: 11 methods checked
+Takaram\Psr7\Internal\ServerRequest: 18 methods checked
+string(6) "!hared"
+string(6) "shared"
+string(6) "!hared"
+string(6) "?hared"
@@ -0,0 +1,37 @@
+--TEST--
+ServerRequest::withParsedBody() keeps objects, arrays and null as given
+--EXTENSIONS--
+psr7_rust
+--FILE--
+<?php
+$request = new Takaram\Psr7\Internal\ServerRequest('POST', '/');
+
+$object = new stdClass();
+$object->name = 'value';
+$withObject = $request->withParsedBody($object);
+var_dump($withObject->getParsedBody() === $object);
+$object->name = 'changed';
+var_dump($withObject->getParsedBody()->name);
+var_dump($withObject->withHeader('X-Foo', 'bar')->getParsedBody() === $object);
+
+var_dump($request->withParsedBody(['a' => 1])->getParsedBody());
+var_dump($withObject->withParsedBody(null)->getParsedBody());
+var_dump($request->getParsedBody());
+
+try {
+    $request->withParsedBody('string');
+} catch (InvalidArgumentException $e) {
+    echo $e->getMessage(), "\n";
+}
+?>
+--EXPECT--
+bool(true)
+string(7) "changed"
+bool(true)
+array(1) {
+  ["a"]=>
+  int(1)
+}
+NULL
+NULL
+Parsed body must be null, an array or an object
@@ -0,0 +1,20 @@
+--TEST--
+Stream::fromBase64() rejects invalid input and round-trips with toBase64()
+--EXTENSIONS--
+psr7_rust
+--FILE--
+<?php
+use Takaram\Psr7\Internal\Stream;
+
+$stream = Stream::fromBase64(base64_encode("\x00\xffbinary"));
+var_dump($stream->toBase64() === base64_encode("\x00\xffbinary"));
+
+try {
+    Stream::fromBase64('not*base64');
+} catch (InvalidArgumentException $e) {
+    var_dump($e->getMessage());
+}
+?>
+--EXPECT--
+bool(true)
+string(19) "Invalid base64 data"
@@ -0,0 +1,19 @@
+--TEST--
+Failed stream operations throw StreamException, a RuntimeException
+--EXTENSIONS--
+psr7_rust
+--FILE--
+<?php
+use Takaram\Psr7\Exception\StreamException;
+
+$stream = new Takaram\Psr7\Internal\Stream('data');
+$stream->detach();
+try {
+    $stream->read(1);
+} catch (StreamException $e) {
+    var_dump($e instanceof RuntimeException, $e->getMessage());
+}
+?>
+--EXPECT--
+bool(true)
+string(22) "Stream is not readable"
@@ -0,0 +1,24 @@
+--TEST--
+Stream::getMetadata('uri') reports the path or wrapper behind the stream
+--EXTENSIONS--
+psr7_rust
+--FILE--
+<?php
+use Takaram\Psr7\Internal\Stream;
+use Takaram\Psr7\Internal\StreamFactory;
+
+$path = tempnam(sys_get_temp_dir(), 'psr7');
+var_dump(Stream::fromFile($path, 'r')->getMetadata('uri') === $path);
+
+$factory = new StreamFactory();
+var_dump($factory->createStreamFromResource(fopen('php://temp', 'r+'))->getMetadata('uri'));
+var_dump((new Stream('contents'))->getMetadata('uri'));
+var_dump($factory->createStream('contents')->getMetadata()['uri']);
+
+unlink($path);
+?>
+--EXPECT--
+bool(true)
+string(10) "php://temp"
+string(12) "php://memory"
+string(12) "php://memory"
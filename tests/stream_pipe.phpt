@@ -0,0 +1,34 @@
+--TEST--
+Stream::pipe() sends the rest of the stream to the output in chunks
+--EXTENSIONS--
+psr7_rust
+--FILE--
+<?php
+$stream = new Takaram\Psr7\Internal\Stream('0123456789abcd');
+$stream->seek(2);
+
+// A chunk size of 1 flushes the buffer after every write, so each call
+// reaches the handler on its own.
+$chunks = [];
+ob_start(function (string $buffer) use (&$chunks) {
+    if ($buffer !== '') {
+        $chunks[] = $buffer;
+    }
+    return '';
+}, 1);
+$written = $stream->pipe(5);
+ob_end_clean();
+
+var_dump($written, $chunks, $stream->eof());
+?>
+--EXPECT--
+int(12)
+array(3) {
+  [0]=>
+  string(5) "23456"
+  [1]=>
+  string(5) "789ab"
+  [2]=>
+  string(2) "cd"
+}
+bool(true)